@@ -101,6 +101,7 @@ fn impl_api_category(ast: &syn::DeriveInput) -> TokenStream {
         |ApiAttribute {
              field,
              name,
+             raw_value,
              type_name,
              with,
              ..
@@ -109,7 +110,7 @@ fn impl_api_category(ast: &syn::DeriveInput) -> TokenStream {
                 let prop_str = prop.to_string();
                 quote! {
                     pub fn #name(&self) -> serde_json::Result<#type_name> {
-                        self.0.decode_field(#prop_str)
+                        self.0.decode_field(#raw_value, #prop_str)
                     }
                 }
             }
@@ -117,13 +118,13 @@ fn impl_api_category(ast: &syn::DeriveInput) -> TokenStream {
                 let prop_str = prop.to_string();
                 quote! {
                     pub fn #name(&self) -> serde_json::Result<#type_name> {
-                        self.0.decode_field_with(#prop_str, #f)
+                        self.0.decode_field_with(#raw_value, #prop_str, #f)
                     }
                 }
             }
             (ApiField::Flattened, None) => quote! {
                 pub fn #name(&self) -> serde_json::Result<#type_name> {
-                    self.0.decode()
+                    self.0.decode(#raw_value)
                 }
             },
             (ApiField::Flattened, Some(_)) => todo!(),
@@ -140,11 +141,27 @@ fn impl_api_category(ast: &syn::DeriveInput) -> TokenStream {
         },
     );
 
+    let from_strs = fields.iter().map(
+        |ApiAttribute {
+             variant, raw_value, ..
+         }| {
+            quote! {
+                #raw_value => Ok(#name::#variant)
+            }
+        },
+    );
+
     let gen = quote! {
         pub struct Response(pub crate::ApiResponse);
 
         impl Response {
             #(#accessors)*
+
+            /// The json returned by the API, for fields which aren't covered by the typed
+            /// accessors.
+            pub fn raw_json(&self) -> &serde_json::Value {
+                &self.0.value
+            }
         }
 
         impl From<crate::ApiResponse> for Response {
@@ -172,6 +189,26 @@ fn impl_api_category(ast: &syn::DeriveInput) -> TokenStream {
                 #category
             }
         }
+
+        impl std::fmt::Display for #name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(crate::ApiSelection::raw_value(*self))
+            }
+        }
+
+        impl std::str::FromStr for #name {
+            type Err = crate::UnknownSelection;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s.to_ascii_lowercase().as_str() {
+                    #(#from_strs,)*
+                    _ => Err(crate::UnknownSelection {
+                        category: #category,
+                        selection: s.to_owned(),
+                    }),
+                }
+            }
+        }
     };
 
     gen.into()
@@ -198,9 +235,7 @@ fn to_static_lt(ty: &mut syn::Type) -> bool {
                 for mut arg in &mut ab.args {
                     match &mut arg {
                         syn::GenericArgument::Type(ty) => {
-                            if to_static_lt(ty) {
-                                res = true;
-                            }
+                            res |= to_static_lt(ty);
                         }
                         syn::GenericArgument::Lifetime(lt) => {
                             lt.ident = syn::Ident::new("static", proc_macro2::Span::call_site());
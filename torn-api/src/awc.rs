@@ -10,6 +10,13 @@ pub enum AwcApiClientError {
 
     #[error(transparent)]
     Payload(#[from] awc::error::JsonPayloadError),
+
+    #[error("Malformed response body ({source}): {body_snippet}")]
+    Deserialize {
+        #[source]
+        source: serde_json::Error,
+        body_snippet: String,
+    },
 }
 
 #[async_trait(?Send)]
@@ -17,6 +24,20 @@ impl ApiClient for awc::Client {
     type Error = AwcApiClientError;
 
     async fn request(&self, url: String) -> Result<serde_json::Value, Self::Error> {
-        self.get(url).send().await?.json().await.map_err(Into::into)
+        let body = self
+            .get(url)
+            .send()
+            .await?
+            .body()
+            .await
+            .map_err(awc::error::JsonPayloadError::Payload)?;
+        if body.is_empty() {
+            return Ok(serde_json::Value::Null);
+        }
+
+        serde_json::from_slice(&body).map_err(|source| AwcApiClientError::Deserialize {
+            source,
+            body_snippet: crate::body_snippet(&body),
+        })
     }
 }
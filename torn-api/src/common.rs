@@ -51,10 +51,30 @@ pub struct Status<'a> {
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Territory {
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub sector: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub size: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub density: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub daily_respect: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub faction: i32,
 
     #[cfg(feature = "decimal")]
@@ -94,28 +114,67 @@ pub struct Attack<'a> {
     pub attacker_id: Option<i32>,
     #[serde(deserialize_with = "de_util::empty_string_int_option")]
     pub attacker_faction: Option<i32>,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub defender_id: i32,
     #[serde(deserialize_with = "de_util::empty_string_int_option")]
     pub defender_faction: Option<i32>,
     pub result: AttackResult,
 
-    #[serde(deserialize_with = "de_util::int_is_bool")]
+    #[cfg_attr(
+        not(feature = "lenient"),
+        serde(deserialize_with = "de_util::int_is_bool")
+    )]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_bool")
+    )]
     pub stealthed: bool,
 
     #[cfg(feature = "decimal")]
     pub respect: rust_decimal::Decimal,
 
     #[cfg(not(feature = "decimal"))]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub respect: f32,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct RespectModifiers {
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub fair_fight: f32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub war: f32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub retaliation: f32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub group_attack: f32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub overseas: f32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub chain_bonus: f32,
 }
 
@@ -139,6 +198,10 @@ pub struct AttackFull<'a> {
     )]
     pub attacker_faction_name: Option<&'a str>,
 
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub defender_id: i32,
     pub defender_name: &'a str,
     #[serde(deserialize_with = "de_util::empty_string_int_option")]
@@ -151,11 +214,32 @@ pub struct AttackFull<'a> {
 
     pub result: AttackResult,
 
-    #[serde(deserialize_with = "de_util::int_is_bool")]
+    #[cfg_attr(
+        not(feature = "lenient"),
+        serde(deserialize_with = "de_util::int_is_bool")
+    )]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_bool")
+    )]
     pub stealthed: bool,
-    #[serde(deserialize_with = "de_util::int_is_bool")]
+    #[cfg_attr(
+        not(feature = "lenient"),
+        serde(deserialize_with = "de_util::int_is_bool")
+    )]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_bool")
+    )]
     pub raid: bool,
-    #[serde(deserialize_with = "de_util::int_is_bool")]
+    #[cfg_attr(
+        not(feature = "lenient"),
+        serde(deserialize_with = "de_util::int_is_bool")
+    )]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_bool")
+    )]
     pub ranked_war: bool,
 
     #[cfg(feature = "decimal")]
@@ -164,8 +248,16 @@ pub struct AttackFull<'a> {
     pub respect_loss: rust_decimal::Decimal,
 
     #[cfg(not(feature = "decimal"))]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub respect: f32,
     #[cfg(not(feature = "decimal"))]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub respect_loss: f32,
 
     pub modifiers: RespectModifiers,
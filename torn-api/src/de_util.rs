@@ -43,6 +43,13 @@ where
     }
 }
 
+pub(crate) fn unix_timestamp<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    chrono::serde::ts_seconds::deserialize(deserializer)
+}
+
 pub(crate) fn int_is_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
 where
     D: Deserializer<'de>,
@@ -56,6 +63,65 @@ where
     }
 }
 
+/// Accepts json booleans, the integers 0 and 1 and the strings "0", "1", "true", "false", "yes"
+/// and "no".
+pub(crate) fn flexible_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct BoolVisitor;
+
+    impl<'de> Visitor<'de> for BoolVisitor {
+        type Value = bool;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(formatter, "boolean, 0 or 1, or a boolean string")
+        }
+
+        fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            Ok(v)
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            match v {
+                0 => Ok(false),
+                1 => Ok(true),
+                x => Err(E::invalid_value(Unexpected::Unsigned(x), &self)),
+            }
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            match v {
+                0 => Ok(false),
+                1 => Ok(true),
+                x => Err(E::invalid_value(Unexpected::Signed(x), &self)),
+            }
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            match v.trim().to_ascii_lowercase().as_str() {
+                "0" | "false" | "no" => Ok(false),
+                "1" | "true" | "yes" => Ok(true),
+                _ => Err(E::invalid_value(Unexpected::Str(v), &self)),
+            }
+        }
+    }
+
+    deserializer.deserialize_any(BoolVisitor)
+}
+
 pub(crate) fn empty_string_int_option<'de, D>(deserializer: D) -> Result<Option<i32>, D::Error>
 where
     D: Deserializer<'de>,
@@ -77,7 +143,7 @@ where
             Ok(Some(v as i32))
         }
 
-        fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E>
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
         where
             E: Error,
         {
@@ -180,6 +246,51 @@ where
     deserializer.deserialize_any(ArrayVisitor(std::marker::PhantomData))
 }
 
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum NumberOrString<'a, N> {
+    Number(N),
+    String(std::borrow::Cow<'a, str>),
+}
+
+impl<N> NumberOrString<'_, N>
+where
+    N: std::str::FromStr,
+{
+    fn into_number<E>(self) -> Result<N, E>
+    where
+        E: serde::de::Error,
+    {
+        match self {
+            Self::Number(n) => Ok(n),
+            Self::String(s) => s
+                .trim()
+                .parse()
+                .map_err(|_| E::invalid_value(Unexpected::Str(&s), &"a numeric string")),
+        }
+    }
+}
+
+/// Accepts both json numbers and strings containing a number.
+pub(crate) fn flexible_number<'de, D, N>(deserializer: D) -> Result<N, D::Error>
+where
+    D: Deserializer<'de>,
+    N: Deserialize<'de> + std::str::FromStr,
+{
+    NumberOrString::<N>::deserialize(deserializer)?.into_number()
+}
+
+/// Like [`flexible_number`], but also accepts `null`.
+pub(crate) fn flexible_number_option<'de, D, N>(deserializer: D) -> Result<Option<N>, D::Error>
+where
+    D: Deserializer<'de>,
+    N: Deserialize<'de> + std::str::FromStr,
+{
+    Option::<NumberOrString<N>>::deserialize(deserializer)?
+        .map(NumberOrString::into_number)
+        .transpose()
+}
+
 pub(crate) fn zero_is_none<'de, D, I>(deserializer: D) -> Result<Option<I>, D::Error>
 where
     D: Deserializer<'de>,
@@ -196,13 +307,58 @@ where
     }
 }
 
-pub(crate) fn null_is_empty_dict<'de, D, K, V>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+/// Accepts maps as well as `null` and `[]`, which the API returns instead of `{}` for some
+/// empty maps.
+pub(crate) fn empty_map<'de, D, M>(deserializer: D) -> Result<M, D::Error>
 where
     D: Deserializer<'de>,
-    K: std::hash::Hash + std::cmp::Eq + Deserialize<'de>,
-    V: Deserialize<'de>,
+    M: Deserialize<'de> + Default,
 {
-    Ok(Option::deserialize(deserializer)?.unwrap_or_default())
+    struct MapVisitor<M>(std::marker::PhantomData<M>);
+
+    impl<'de, M> Visitor<'de> for MapVisitor<M>
+    where
+        M: Deserialize<'de> + Default,
+    {
+        type Value = M;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(formatter, "map, empty array or null")
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            Ok(M::default())
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            Ok(M::default())
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+        {
+            match seq.next_element::<serde::de::IgnoredAny>()? {
+                None => Ok(M::default()),
+                Some(_) => Err(A::Error::invalid_type(Unexpected::Seq, &self)),
+            }
+        }
+
+        fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::MapAccess<'de>,
+        {
+            M::deserialize(serde::de::value::MapAccessDeserializer::new(map))
+        }
+    }
+
+    deserializer.deserialize_any(MapVisitor(std::marker::PhantomData))
 }
 
 #[cfg(feature = "decimal")]
@@ -8,7 +8,7 @@ use serde::{
 
 use torn_api_macros::{ApiCategory, IntoOwned};
 
-use crate::de_util::{self, null_is_empty_dict};
+use crate::de_util::{self, empty_map, unix_timestamp};
 
 pub use crate::common::{Attack, AttackFull, LastAction, Status, Territory};
 
@@ -19,21 +19,47 @@ pub enum FactionSelection {
     #[api(type = "Basic", flatten)]
     Basic,
 
-    #[api(type = "BTreeMap<i32, Attack>", field = "attacks")]
+    #[api(type = "BTreeMap<i32, Attack>", field = "attacks", with = "empty_map")]
     AttacksFull,
 
-    #[api(type = "BTreeMap<i32, AttackFull>", field = "attacks")]
+    #[api(
+        type = "BTreeMap<i32, AttackFull>",
+        field = "attacks",
+        with = "empty_map"
+    )]
     Attacks,
 
+    #[api(type = "BTreeMap<i32, Revive>", field = "revives", with = "empty_map")]
+    RevivesFull,
+
+    #[api(
+        type = "BTreeMap<i32, ReviveFull>",
+        field = "revives",
+        with = "empty_map"
+    )]
+    Revives,
+
     #[api(
         type = "HashMap<String, Territory>",
         field = "territory",
-        with = "null_is_empty_dict"
+        with = "empty_map"
     )]
     Territory,
 
     #[api(type = "Option<Chain>", field = "chain", with = "deserialize_chain")]
     Chain,
+
+    /// The names of all selections of the category. The API doesn't offer a way to look up
+    /// factions by name.
+    #[api(type = "Vec<&str>", field = "selections")]
+    Lookup,
+
+    #[api(
+        type = "chrono::DateTime<chrono::Utc>",
+        field = "timestamp",
+        with = "unix_timestamp"
+    )]
+    Timestamp,
 }
 
 pub type Selection = FactionSelection;
@@ -41,7 +67,15 @@ pub type Selection = FactionSelection;
 #[derive(Debug, IntoOwned, Deserialize)]
 pub struct Member<'a> {
     pub name: &'a str,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub level: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub days_in_faction: i16,
     pub position: &'a str,
     pub status: Status<'a>,
@@ -50,11 +84,31 @@ pub struct Member<'a> {
 
 #[derive(Debug, IntoOwned, Deserialize)]
 pub struct FactionTerritoryWar<'a> {
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub territory_war_id: i32,
     pub territory: &'a str,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub assaulting_faction: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub defending_faction: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub score: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub required_score: i32,
 
     #[serde(with = "chrono::serde::ts_seconds")]
@@ -64,22 +118,144 @@ pub struct FactionTerritoryWar<'a> {
     pub end_time: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviveResult {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Revive<'a> {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub timestamp: DateTime<Utc>,
+    pub result: ReviveResult,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
+    pub chance: f32,
+
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
+    pub reviver_id: i32,
+    #[serde(deserialize_with = "de_util::zero_is_none")]
+    pub reviver_faction: Option<i32>,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
+    pub target_id: i32,
+    #[serde(deserialize_with = "de_util::zero_is_none")]
+    pub target_faction: Option<i32>,
+
+    pub target_hospital_reason: &'a str,
+    #[cfg_attr(
+        not(feature = "lenient"),
+        serde(deserialize_with = "de_util::int_is_bool")
+    )]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_bool")
+    )]
+    pub target_early_discharge: bool,
+    pub target_last_action: LastAction,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReviveFull<'a> {
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub timestamp: DateTime<Utc>,
+    pub result: ReviveResult,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
+    pub chance: f32,
+
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
+    pub reviver_id: i32,
+    pub reviver_name: &'a str,
+    #[serde(deserialize_with = "de_util::zero_is_none")]
+    pub reviver_faction: Option<i32>,
+    #[serde(
+        deserialize_with = "de_util::empty_string_is_none",
+        rename = "reviver_factionname"
+    )]
+    pub reviver_faction_name: Option<&'a str>,
+
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
+    pub target_id: i32,
+    pub target_name: &'a str,
+    #[serde(deserialize_with = "de_util::zero_is_none")]
+    pub target_faction: Option<i32>,
+    #[serde(
+        deserialize_with = "de_util::empty_string_is_none",
+        rename = "target_factionname"
+    )]
+    pub target_faction_name: Option<&'a str>,
+
+    pub target_hospital_reason: &'a str,
+    #[cfg_attr(
+        not(feature = "lenient"),
+        serde(deserialize_with = "de_util::int_is_bool")
+    )]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_bool")
+    )]
+    pub target_early_discharge: bool,
+    pub target_last_action: LastAction,
+}
+
 #[derive(Debug, IntoOwned, Deserialize)]
 pub struct Basic<'a> {
     #[serde(rename = "ID")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub id: i32,
     pub name: &'a str,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub leader: i32,
 
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub respect: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub age: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub capacity: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub best_chain: i32,
 
     #[serde(deserialize_with = "de_util::empty_string_is_none")]
     pub tag_image: Option<&'a str>,
 
-    #[serde(borrow)]
+    #[serde(borrow, deserialize_with = "de_util::empty_map")]
     pub members: BTreeMap<i32, Member<'a>>,
 
     #[serde(deserialize_with = "de_util::datetime_map")]
@@ -89,12 +265,98 @@ pub struct Basic<'a> {
     pub territory_wars: Vec<FactionTerritoryWar<'a>>,
 }
 
+impl Response {
+    /// The oldest timestamp of the log returned for `selection`, or `None` if it's empty.
+    fn oldest_timestamp(&self, selection: Selection) -> serde_json::Result<Option<i64>> {
+        match selection {
+            Selection::Attacks => self.attacks().map(|attacks| {
+                attacks
+                    .values()
+                    .map(|a| a.timestamp_started.timestamp())
+                    .min()
+            }),
+            Selection::AttacksFull => self.attacks_full().map(|attacks| {
+                attacks
+                    .values()
+                    .map(|a| a.timestamp_started.timestamp())
+                    .min()
+            }),
+            Selection::Revives => self
+                .revives()
+                .map(|revives| revives.values().map(|r| r.timestamp.timestamp()).min()),
+            Selection::RevivesFull => self
+                .revives_full()
+                .map(|revives| revives.values().map(|r| r.timestamp.timestamp()).min()),
+            _ => Ok(None),
+        }
+    }
+
+    /// The members of a faction from the `basic` selection, without decoding the rest of it.
+    pub fn members_map(&self) -> serde_json::Result<BTreeMap<i32, Member<'_>>> {
+        self.0.decode_field("basic", "members")
+    }
+}
+
+/// Walks backwards through the paged log of one of the two `log` selections, requesting every
+/// page through `fetch`. If `build` selects neither, the first one is added. Used by the
+/// `*_history` methods of both providers.
+pub(crate) fn history<'a, B, X, Fut, E>(
+    build: B,
+    log: [Selection; 2],
+    fetch: X,
+) -> impl futures::Stream<Item = Result<Response, E>> + 'a
+where
+    B: Fn(crate::ApiRequestBuilder<Selection>) -> crate::ApiRequestBuilder<Selection> + 'a,
+    X: Fn(crate::ApiRequestBuilder<Selection>) -> Fut + 'a,
+    Fut: std::future::Future<Output = Result<Response, E>> + 'a,
+    E: crate::FromResponseError + 'a,
+{
+    use crate::ApiSelection;
+
+    futures::stream::unfold(Some((build, fetch, None)), move |state| async move {
+        let (build, fetch, to) = state?;
+
+        let mut builder = build(crate::ApiRequestBuilder::default());
+        let selection = match log
+            .into_iter()
+            .find(|s| builder.request.selections.contains(&s.raw_value()))
+        {
+            Some(selection) => selection,
+            None => {
+                builder = builder.selections([log[0]]);
+                log[0]
+            }
+        };
+        if let Some(to) = to {
+            builder = builder.to_timestamp(to);
+        }
+
+        let response = match fetch(builder).await {
+            Ok(response) => response,
+            Err(why) => return Some((Err(why), None)),
+        };
+
+        let oldest = match response.oldest_timestamp(selection) {
+            Ok(oldest) => oldest,
+            Err(why) => return Some((Err(E::from_response_error(why.into())), None)),
+        };
+
+        match (oldest, to) {
+            (None, _) => None,
+            (Some(oldest), Some(to)) if oldest >= to => Some((Ok(response), None)),
+            (Some(oldest), _) => Some((Ok(response), Some((build, fetch, Some(oldest))))),
+        }
+    })
+}
+
 #[derive(Debug)]
 pub struct Chain {
     pub current: i32,
     pub max: i32,
     #[cfg(feature = "decimal")]
     pub modifier: rust_decimal::Decimal,
+    #[cfg(not(feature = "decimal"))]
+    pub modifier: f32,
     pub timeout: Option<i32>,
     pub cooldown: Option<i32>,
     pub start: DateTime<Utc>,
@@ -134,7 +396,6 @@ where
 
             let mut current = None;
             let mut max = None;
-            #[cfg(feature = "decimal")]
             let mut modifier = None;
             let mut timeout = None;
             let mut cooldown = None;
@@ -153,10 +414,7 @@ where
                         max = Some(map.next_value()?);
                     }
                     Fields::Modifier => {
-                        #[cfg(feature = "decimal")]
-                        {
-                            modifier = Some(map.next_value()?);
-                        }
+                        modifier = Some(map.next_value()?);
                     }
                     Fields::Timeout => {
                         match map.next_value()? {
@@ -198,7 +456,6 @@ where
             Ok(Some(Chain {
                 current,
                 max,
-                #[cfg(feature = "decimal")]
                 modifier: modifier.ok_or_else(|| A::Error::missing_field("modifier"))?,
                 timeout,
                 cooldown,
@@ -216,6 +473,249 @@ mod tests {
     use super::*;
     use crate::tests::{async_test, setup, Client, ClientTrait};
 
+    #[test]
+    fn lookup() {
+        let response = Response::from(
+            crate::ApiResponse::from_value(serde_json::json!({
+                "selections": ["applications", "armor", "basic", "chain", "lookup"]
+            }))
+            .unwrap(),
+        );
+
+        let selections = response.lookup().unwrap();
+        assert!(selections.contains(&"basic"));
+        assert!(selections
+            .iter()
+            .filter_map(|s| s.parse::<Selection>().ok())
+            .any(|s| matches!(s, Selection::Chain)));
+    }
+
+    #[test]
+    fn chain_modifier() {
+        let response = Response::from(
+            crate::ApiResponse::from_value(serde_json::json!({
+                "chain": {
+                    "current": 25,
+                    "max": 50,
+                    "modifier": 1.25,
+                    "timeout": 270,
+                    "cooldown": 0,
+                    "start": 1700000000,
+                    "end": 1700000300
+                }
+            }))
+            .unwrap(),
+        );
+
+        let chain = response.chain().unwrap().unwrap();
+        assert_eq!(chain.modifier.to_string(), "1.25");
+        assert_eq!(chain.cooldown, None);
+    }
+
+    #[test]
+    fn members_map() {
+        let response = Response::from(
+            crate::ApiResponse::from_value(serde_json::json!({
+                "ID": 1,
+                "members": {
+                    "2111649": {
+                        "name": "Pyrit",
+                        "level": 100,
+                        "days_in_faction": 365,
+                        "position": "Leader",
+                        "status": {
+                            "description": "Okay",
+                            "details": "",
+                            "state": "Okay",
+                            "color": "green",
+                            "until": 0
+                        },
+                        "last_action": {
+                            "status": "Online",
+                            "timestamp": 1700000000,
+                            "relative": "0 minutes ago"
+                        }
+                    }
+                }
+            }))
+            .unwrap(),
+        );
+
+        let members = response.members_map().unwrap();
+        assert_eq!(members[&2111649].name, "Pyrit");
+    }
+
+    #[test]
+    fn revives_history() {
+        use crate::send::ApiClient;
+        use futures::StreamExt;
+
+        struct RevivesClient;
+
+        #[async_trait::async_trait]
+        impl crate::send::ApiClient for RevivesClient {
+            type Error = std::convert::Infallible;
+
+            async fn request(&self, url: String) -> Result<serde_json::Value, Self::Error> {
+                assert!(url.contains("selections=revivesfull"));
+
+                let revive = |timestamp: i64| {
+                    serde_json::json!({
+                        "timestamp": timestamp,
+                        "result": "success",
+                        "chance": 87.5,
+                        "reviver_id": 1,
+                        "reviver_faction": 2,
+                        "target_id": 3,
+                        "target_faction": 0,
+                        "target_hospital_reason": "Mugged by someone",
+                        "target_early_discharge": 0,
+                        "target_last_action": { "status": "Offline", "timestamp": timestamp }
+                    })
+                };
+
+                Ok(
+                    match url.split('&').find_map(|item| item.strip_prefix("to=")) {
+                        None => {
+                            serde_json::json!({ "revives": { "5": revive(500), "4": revive(400) } })
+                        }
+                        Some("400") => serde_json::json!({ "revives": { "4": revive(400) } }),
+                        Some(_) => serde_json::json!({ "revives": [] }),
+                    },
+                )
+            }
+        }
+
+        let pages = futures::executor::block_on(
+            RevivesClient
+                .torn_api("")
+                .faction_revives_history(|b| b)
+                .collect::<Vec<_>>(),
+        );
+
+        assert_eq!(pages.len(), 2);
+        let revives = pages[0].as_ref().unwrap().revives_full().unwrap();
+        assert_eq!(revives[&5].result, ReviveResult::Success);
+        assert_eq!(revives[&5].target_faction, None);
+        assert!(!revives[&4].target_early_discharge);
+    }
+
+    #[cfg(feature = "user")]
+    #[test]
+    fn members_with_user_basic() {
+        use crate::send::{ApiClient, RequestExecutor};
+        use crate::{ApiClientError, ApiRequest, ApiSelection, DirectExecutor, ResponseError};
+
+        struct FactionClient;
+
+        #[async_trait::async_trait]
+        impl ApiClient for FactionClient {
+            type Error = std::convert::Infallible;
+
+            async fn request(&self, url: String) -> Result<serde_json::Value, Self::Error> {
+                let status = serde_json::json!({
+                    "description": "Okay",
+                    "details": "",
+                    "state": "Okay",
+                    "color": "green",
+                    "until": 0
+                });
+                let member = |name: &str| {
+                    serde_json::json!({
+                        "name": name,
+                        "level": 1,
+                        "days_in_faction": 1,
+                        "position": "Member",
+                        "status": status,
+                        "last_action": {
+                            "status": "Offline",
+                            "timestamp": 1700000000,
+                            "relative": "1 day ago"
+                        }
+                    })
+                };
+
+                Ok(if url.contains("/faction/") {
+                    serde_json::json!({
+                        "ID": 1,
+                        "members": {
+                            "1": member("first"),
+                            "2": member("second"),
+                            "3": member("third")
+                        }
+                    })
+                } else if url.contains("/user/1?") {
+                    serde_json::json!({
+                        "player_id": 1,
+                        "name": "first",
+                        "level": 1,
+                        "gender": "Male",
+                        "status": status
+                    })
+                } else {
+                    serde_json::json!({ "error": { "code": 6, "error": "Incorrect ID" } })
+                })
+            }
+        }
+
+        /// Loses the response for the id 3 of bulk requests.
+        struct LossyExecutor(DirectExecutor<FactionClient>);
+
+        #[async_trait::async_trait]
+        impl RequestExecutor<FactionClient> for LossyExecutor {
+            type Error = ApiClientError<std::convert::Infallible>;
+
+            async fn execute<A>(
+                &self,
+                client: &FactionClient,
+                request: ApiRequest<A>,
+                id: Option<String>,
+            ) -> Result<A::Response, Self::Error>
+            where
+                A: ApiSelection,
+            {
+                self.0.execute(client, request, id).await
+            }
+
+            async fn execute_many<A, I>(
+                &self,
+                client: &FactionClient,
+                request: ApiRequest<A>,
+                ids: Vec<I>,
+            ) -> HashMap<I, Result<A::Response, Self::Error>>
+            where
+                A: ApiSelection,
+                I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+            {
+                let mut responses = self.0.execute_many(client, request, ids).await;
+                responses.retain(|id, _| id.to_string() != "3");
+                responses
+            }
+        }
+
+        let members = futures::executor::block_on(
+            crate::send::ApiProvider::new(
+                &FactionClient,
+                LossyExecutor(DirectExecutor::new(String::new())),
+            )
+            .faction_members_with_user_basic(Some(1)),
+        )
+        .unwrap();
+
+        assert_eq!(members.len(), 3);
+        let (member, basic) = &members[&1];
+        assert_eq!(member.name, "first");
+        assert_eq!(basic.as_ref().unwrap().name, "first");
+        assert_eq!(
+            members[&2].1.as_ref().err().and_then(|e| e.api_code()),
+            Some(6)
+        );
+        assert!(matches!(
+            &members[&3].1,
+            Err(ApiClientError::Response(ResponseError::MissingBulkResponse { id })) if id == "3"
+        ));
+    }
+
     #[async_test]
     async fn faction() {
         let key = setup();
@@ -258,6 +758,25 @@ mod tests {
         response.chain().unwrap();
     }
 
+    #[async_test]
+    async fn attacks_history() {
+        use futures::StreamExt;
+
+        let key = setup();
+
+        let client = Client::default();
+        let provider = client.torn_api(key);
+        let pages: Vec<_> = provider
+            .faction_attacks_history(|b| b.selections([Selection::Attacks]))
+            .take(2)
+            .collect()
+            .await;
+
+        for page in pages {
+            page.unwrap().attacks().unwrap();
+        }
+    }
+
     #[async_test]
     async fn destroyed_faction() {
         let key = setup();
@@ -17,7 +17,10 @@ where
     }
 }
 
-impl<T> IntoOwned for Vec<T> where T: IntoOwned {
+impl<T> IntoOwned for Vec<T>
+where
+    T: IntoOwned,
+{
     type Owned = Vec<<T as IntoOwned>::Owned>;
 
     fn into_owned(self) -> Self::Owned {
@@ -27,9 +30,13 @@ impl<T> IntoOwned for Vec<T> where T: IntoOwned {
         }
         owned
     }
-} 
+}
 
-impl<K, V> IntoOwned for std::collections::HashMap<K, V> where V: IntoOwned, K: Eq + std::hash::Hash {
+impl<K, V> IntoOwned for std::collections::HashMap<K, V>
+where
+    V: IntoOwned,
+    K: Eq + std::hash::Hash,
+{
     type Owned = std::collections::HashMap<K, <V as IntoOwned>::Owned>;
 
     fn into_owned(self) -> Self::Owned {
@@ -37,7 +44,11 @@ impl<K, V> IntoOwned for std::collections::HashMap<K, V> where V: IntoOwned, K:
     }
 }
 
-impl<K, V> IntoOwned for std::collections::BTreeMap<K, V> where V: IntoOwned, K: Eq + Ord + std::hash::Hash  {
+impl<K, V> IntoOwned for std::collections::BTreeMap<K, V>
+where
+    V: IntoOwned,
+    K: Eq + Ord + std::hash::Hash,
+{
     type Owned = std::collections::BTreeMap<K, <V as IntoOwned>::Owned>;
 
     fn into_owned(self) -> Self::Owned {
@@ -45,7 +56,10 @@ impl<K, V> IntoOwned for std::collections::BTreeMap<K, V> where V: IntoOwned, K:
     }
 }
 
-impl<Z> IntoOwned for chrono::DateTime<Z> where Z: chrono::TimeZone {
+impl<Z> IntoOwned for chrono::DateTime<Z>
+where
+    Z: chrono::TimeZone,
+{
     type Owned = Self;
 
     fn into_owned(self) -> Self::Owned {
@@ -53,7 +67,7 @@ impl<Z> IntoOwned for chrono::DateTime<Z> where Z: chrono::TimeZone {
     }
 }
 
-impl<'a> IntoOwned for &'a str {
+impl IntoOwned for &str {
     type Owned = String;
 
     fn into_owned(self) -> Self::Owned {
@@ -11,6 +11,15 @@ pub enum Selection {
     Info,
 }
 
+/// The values of [`Info::access_level`].
+pub mod access_level {
+    pub const CUSTOM: i16 = 0;
+    pub const PUBLIC: i16 = 1;
+    pub const MINIMAL: i16 = 2;
+    pub const LIMITED: i16 = 3;
+    pub const FULL: i16 = 4;
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[non_exhaustive]
 pub enum AccessType {
@@ -229,13 +238,101 @@ pub struct Selections {
     pub key: HashSet<KeySelection>,
 }
 
+impl Selections {
+    /// Checks whether the selection of the given category is included, e.g.
+    /// `contains("faction", "donations")`. Unknown categories and selections are never
+    /// included.
+    pub fn contains(&self, category: &str, selection: &str) -> bool {
+        fn parse<'de, T>(selection: &'de str) -> Option<T>
+        where
+            T: Deserialize<'de>,
+        {
+            use serde::de::IntoDeserializer;
+            T::deserialize(
+                IntoDeserializer::<serde::de::value::Error>::into_deserializer(selection),
+            )
+            .ok()
+        }
+
+        match category {
+            "user" => parse(selection)
+                .filter(|s| *s != UserSelection::Unknown)
+                .is_some_and(|s| self.user.contains(&s)),
+            "faction" => parse(selection)
+                .filter(|s| *s != FactionSelection::Unknown)
+                .is_some_and(|s| self.faction.contains(&s)),
+            "company" => parse(selection)
+                .filter(|s| *s != CompanySelection::Unknown)
+                .is_some_and(|s| self.company.contains(&s)),
+            "torn" => parse(selection)
+                .filter(|s| *s != TornSelection::Unknown)
+                .is_some_and(|s| self.torn.contains(&s)),
+            "market" => parse(selection)
+                .filter(|s| *s != MarketSelection::Unknown)
+                .is_some_and(|s| self.market.contains(&s)),
+            "property" => parse(selection)
+                .filter(|s| *s != PropertySelection::Unknown)
+                .is_some_and(|s| self.property.contains(&s)),
+            "key" => parse(selection).is_some_and(|s: KeySelection| self.key.contains(&s)),
+            _ => false,
+        }
+    }
+}
+
+/// A request contained a selection which the key isn't permitted to access.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("the key can't access the {category} selection `{selection}`")]
+pub struct InaccessibleSelection {
+    pub category: &'static str,
+    pub selection: &'static str,
+}
+
+impl<A> crate::ApiRequestBuilder<A>
+where
+    A: crate::ApiSelection,
+{
+    /// Checks the requested selections against the selections of a key, as returned by
+    /// [`Selection::Info`]. Raw selections which are unknown to this crate are rejected as well.
+    pub fn validate_access(self, selections: &Selections) -> Result<Self, InaccessibleSelection> {
+        let category = A::category();
+        match self
+            .request
+            .selections
+            .iter()
+            .find(|s| !selections.contains(category, s))
+        {
+            Some(selection) => Err(InaccessibleSelection {
+                category,
+                selection,
+            }),
+            None => Ok(self),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Info {
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "crate::de_util::flexible_number")
+    )]
     pub access_level: i16,
     pub access_type: AccessType,
     pub selections: Selections,
 }
 
+impl Info {
+    pub fn is_full_access(&self) -> bool {
+        self.access_type == AccessType::Full || self.access_level >= access_level::FULL
+    }
+
+    /// Whether the key may request the selection of the given category. See
+    /// [`Selections::contains`].
+    pub fn can_access(&self, category: &str, selection: &str) -> bool {
+        self.selections.contains(category, selection)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -253,4 +350,64 @@ mod tests {
 
         response.info().unwrap();
     }
+
+    #[test]
+    fn access() {
+        let info: Info = serde_json::from_value(serde_json::json!({
+            "access_level": 1,
+            "access_type": "Public Only",
+            "selections": {
+                "user": ["basic", "profile", "lookup", "timestamp"],
+                "faction": ["basic"],
+                "company": [],
+                "torn": ["items"],
+                "market": [],
+                "property": [],
+                "key": ["info"],
+            }
+        }))
+        .unwrap();
+
+        assert!(!info.is_full_access());
+        assert_eq!(info.access_level, access_level::PUBLIC);
+        assert!(info.can_access("user", "profile"));
+        assert!(info.can_access("key", "info"));
+        assert!(!info.can_access("faction", "donations"));
+        assert!(!info.can_access("faction", "notaselection"));
+        assert!(!info.can_access("racing", "cars"));
+    }
+
+    #[cfg(feature = "faction")]
+    #[test]
+    fn validate_access() {
+        let selections: Selections = serde_json::from_value(serde_json::json!({
+            "user": [],
+            "faction": ["basic", "chain"],
+            "company": [],
+            "torn": [],
+            "market": [],
+            "property": [],
+            "key": [],
+        }))
+        .unwrap();
+
+        let builder =
+            crate::ApiRequestBuilder::<crate::faction::Selection>::default().selections([
+                crate::faction::Selection::Basic,
+                crate::faction::Selection::Chain,
+            ]);
+        assert!(builder.validate_access(&selections).is_ok());
+
+        let err = crate::ApiRequestBuilder::<crate::faction::Selection>::default()
+            .selections([crate::faction::Selection::Basic])
+            .raw_selection("donations")
+            .validate_access(&selections)
+            .err()
+            .unwrap();
+        assert_eq!(err.selection, "donations");
+        assert_eq!(
+            err.to_string(),
+            "the key can't access the faction selection `donations`"
+        );
+    }
 }
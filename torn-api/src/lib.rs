@@ -1,6 +1,9 @@
 #![warn(clippy::all, clippy::perf, clippy::style, clippy::suspicious)]
 
 pub mod into_owned;
+/// `?Send` counterparts of the traits in [`send`], for clients like `awc` whose futures can't be
+/// sent between threads.
+#[cfg(any(feature = "local", all(feature = "reqwest", target_arch = "wasm32")))]
 pub mod local;
 pub mod send;
 
@@ -30,7 +33,7 @@ pub mod common;
 
 mod de_util;
 
-use std::fmt::Write;
+use std::{borrow::Borrow, fmt::Write};
 
 use chrono::{DateTime, Utc};
 use serde::{de::Error as DeError, Deserialize};
@@ -38,6 +41,22 @@ use thiserror::Error;
 
 pub use into_owned::IntoOwned;
 
+/// Returns at most the first 256 bytes of a response body for use in error messages.
+pub(crate) fn body_snippet(body: &[u8]) -> String {
+    const MAX_LEN: usize = 256;
+
+    let body = String::from_utf8_lossy(body);
+    if body.len() <= MAX_LEN {
+        return body.into_owned();
+    }
+
+    let mut end = MAX_LEN;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &body[..end])
+}
+
 pub struct ApiResponse {
     pub value: serde_json::Value,
 }
@@ -49,6 +68,12 @@ pub enum ResponseError {
 
     #[error(transparent)]
     MalformedResponse(#[from] serde_json::Error),
+
+    #[error("API returned an empty response")]
+    EmptyResponse,
+
+    #[error("No response was returned for id {id} of a bulk request")]
+    MissingBulkResponse { id: String },
 }
 
 impl ResponseError {
@@ -58,6 +83,57 @@ impl ResponseError {
             _ => None,
         }
     }
+
+    /// The message sent by the API, falling back to the documented description of the error
+    /// code if the message was empty.
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            Self::Api { reason, .. } if !reason.is_empty() => Some(reason),
+            Self::Api { code, .. } => Some(api_error_description(*code)),
+            _ => None,
+        }
+    }
+}
+
+/// Descriptions of the API error codes as given in the API documentation. Services which need
+/// to handle newer codes can chain this with their own table.
+pub const API_ERROR_CODES: &[(u8, &str)] = &[
+    (0, "Unknown error"),
+    (1, "Key is empty"),
+    (2, "Incorrect Key"),
+    (3, "Wrong type"),
+    (4, "Wrong fields"),
+    (5, "Too many requests"),
+    (6, "Incorrect ID"),
+    (7, "Incorrect ID-entity relation"),
+    (8, "IP block"),
+    (9, "API disabled"),
+    (10, "Key owner is in federal jail"),
+    (11, "Key change error"),
+    (12, "Key read error"),
+    (
+        13,
+        "The key is temporarily disabled due to owner inactivity",
+    ),
+    (14, "Daily read limit reached"),
+    (15, "Temporary error"),
+    (16, "Access level of this key is not high enough"),
+    (17, "Backend error occurred, please try again"),
+    (18, "API key has been paused by the owner"),
+    (19, "Must be migrated to crimes 2.0"),
+    (20, "Race not yet finished"),
+    (21, "Incorrect category"),
+    (22, "This selection is only available in API v1"),
+    (23, "This selection is only available in API v2"),
+    (24, "Closed temporarily"),
+];
+
+/// The description of an API error code as given in the API documentation.
+pub fn api_error_description(code: u8) -> &'static str {
+    API_ERROR_CODES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map_or("Unknown error", |(_, description)| description)
 }
 
 impl ApiResponse {
@@ -65,9 +141,13 @@ impl ApiResponse {
         #[derive(serde::Deserialize)]
         struct ApiErrorDto {
             code: u8,
-            #[serde(rename = "error")]
+            #[serde(rename = "error", default)]
             reason: String,
         }
+        if value.is_null() {
+            return Err(ResponseError::EmptyResponse);
+        }
+
         match value.get_mut("error") {
             Some(error) => {
                 let dto: ApiErrorDto = serde_json::from_value(error.take())?;
@@ -80,34 +160,161 @@ impl ApiResponse {
         }
     }
 
+    /// Decodes the whole response as the data of `selection`. If a field is missing, which
+    /// usually means that the selection wasn't requested, the error names the selection.
     #[allow(dead_code)]
-    fn decode<'de, D>(&'de self) -> serde_json::Result<D>
+    fn decode<'de, D>(&'de self, selection: &'static str) -> serde_json::Result<D>
     where
         D: Deserialize<'de>,
     {
-        D::deserialize(&self.value)
+        D::deserialize(&self.value).map_err(|why| {
+            if why.to_string().starts_with("missing field") {
+                missing_selection(selection, why)
+            } else {
+                serde_json::Error::custom(format_args!(
+                    "{why}: {}",
+                    body_snippet(self.value.to_string().as_bytes())
+                ))
+            }
+        })
     }
 
     #[allow(dead_code)]
-    fn decode_field<'de, D>(&'de self, field: &'static str) -> serde_json::Result<D>
+    fn decode_field<'de, D>(
+        &'de self,
+        selection: &'static str,
+        field: &'static str,
+    ) -> serde_json::Result<D>
     where
         D: Deserialize<'de>,
     {
-        self.value
-            .get(field)
-            .ok_or_else(|| serde_json::Error::missing_field(field))
-            .and_then(D::deserialize)
+        self.decode_field_with(selection, field, D::deserialize)
     }
 
+    /// Decodes `field` of `selection` with `fun`. Errors include a snippet of the json of the
+    /// field, since serde's errors don't say which value they failed on.
     #[allow(dead_code)]
-    fn decode_field_with<'de, V, F>(&'de self, field: &'static str, fun: F) -> serde_json::Result<V>
+    fn decode_field_with<'de, V, F>(
+        &'de self,
+        selection: &'static str,
+        field: &'static str,
+        fun: F,
+    ) -> serde_json::Result<V>
     where
         F: FnOnce(&'de serde_json::Value) -> serde_json::Result<V>,
     {
-        self.value
+        let value = self
+            .value
             .get(field)
-            .ok_or_else(|| serde_json::Error::missing_field(field))
-            .and_then(fun)
+            .ok_or_else(|| missing_selection(selection, serde_json::Error::missing_field(field)))?;
+
+        fun(value).map_err(|why| {
+            serde_json::Error::custom(format_args!(
+                "{why} in `{field}`: {}",
+                body_snippet(value.to_string().as_bytes())
+            ))
+        })
+    }
+}
+
+#[allow(dead_code)]
+fn missing_selection(selection: &'static str, why: serde_json::Error) -> serde_json::Error {
+    serde_json::Error::custom(format_args!(
+        "selection `{selection}` is missing from the response ({why})"
+    ))
+}
+
+pub trait IntoTimestamp {
+    fn into_timestamp(self) -> i64;
+}
+
+impl IntoTimestamp for i64 {
+    fn into_timestamp(self) -> i64 {
+        self
+    }
+}
+
+impl IntoTimestamp for i32 {
+    fn into_timestamp(self) -> i64 {
+        self.into()
+    }
+}
+
+impl IntoTimestamp for DateTime<Utc> {
+    fn into_timestamp(self) -> i64 {
+        self.timestamp()
+    }
+}
+
+/// The time of the API server, as returned by the `timestamp` selections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ServerTime(pub DateTime<Utc>);
+
+impl IntoTimestamp for ServerTime {
+    fn into_timestamp(self) -> i64 {
+        self.0.timestamp()
+    }
+}
+
+/// Implemented by executor errors so that convenience methods which decode the response
+/// themselves can report decoding failures.
+pub trait FromResponseError {
+    fn from_response_error(error: ResponseError) -> Self;
+}
+
+impl<C> FromResponseError for ApiClientError<C>
+where
+    C: std::error::Error,
+{
+    fn from_response_error(error: ResponseError) -> Self {
+        Self::Response(error)
+    }
+}
+
+/// A list of ids which is rendered as a comma separated path segment, for selections that accept
+/// more than one id at a time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdList<T = i32>(pub Vec<T>);
+
+impl<T> std::fmt::Display for IdList<T>
+where
+    T: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, id) in self.0.iter().enumerate() {
+            if idx != 0 {
+                f.write_char(',')?;
+            }
+            write!(f, "{id}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> From<Vec<T>> for IdList<T> {
+    fn from(value: Vec<T>) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> From<&[T]> for IdList<T>
+where
+    T: Clone,
+{
+    fn from(value: &[T]) -> Self {
+        Self(value.to_vec())
+    }
+}
+
+impl<T, const N: usize> From<[T; N]> for IdList<T> {
+    fn from(value: [T; N]) -> Self {
+        Self(value.into())
+    }
+}
+
+impl<T> FromIterator<T> for IdList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
     }
 }
 
@@ -137,6 +344,228 @@ impl<C> DirectExecutor<C> {
     }
 }
 
+/// Spreads requests over a handful of keys without the need for a key pool. The keys are used
+/// round-robin, and a key which hits the rate limit (code 5) is skipped for a minute while the
+/// request is retried with the next one. Requests fail with code 5 once every key is rate
+/// limited.
+pub struct RotatingExecutor<C> {
+    keys: Vec<String>,
+    state: std::sync::Mutex<Rotation>,
+    _marker: std::marker::PhantomData<C>,
+}
+
+struct Rotation {
+    next: usize,
+    limited_until: Vec<Option<std::time::Instant>>,
+}
+
+impl<C> RotatingExecutor<C> {
+    pub fn new<I, S>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: ToString,
+    {
+        let keys: Vec<_> = keys.into_iter().map(|k| k.to_string()).collect();
+        Self {
+            state: std::sync::Mutex::new(Rotation {
+                next: 0,
+                limited_until: vec![None; keys.len()],
+            }),
+            keys,
+            _marker: Default::default(),
+        }
+    }
+
+    /// The next key which isn't rate limited, if any.
+    fn next_key(&self) -> Result<&str, ResponseError> {
+        let mut state = self.state.lock().unwrap();
+        let now = std::time::Instant::now();
+        for offset in 0..self.keys.len() {
+            let index = (state.next + offset) % self.keys.len();
+            match state.limited_until[index] {
+                Some(until) if until > now => continue,
+                _ => {
+                    state.limited_until[index] = None;
+                    state.next = index + 1;
+                    return Ok(&self.keys[index]);
+                }
+            }
+        }
+
+        Err(ResponseError::Api {
+            code: 5,
+            reason: api_error_description(5).to_owned(),
+        })
+    }
+
+    fn rate_limited(&self, key: &str) {
+        if let Some(index) = self.keys.iter().position(|k| k == key) {
+            self.state.lock().unwrap().limited_until[index] =
+                Some(std::time::Instant::now() + std::time::Duration::from_secs(60));
+        }
+    }
+}
+
+/// Wraps another executor and calls `on_slow` with the path and duration of every request which
+/// took longer than `threshold`. Bulk requests are timed as a whole.
+pub struct TimingExecutor<E> {
+    inner: E,
+    threshold: std::time::Duration,
+    #[allow(clippy::type_complexity)]
+    on_slow: Box<dyn Fn(&str, std::time::Duration) + Send + Sync>,
+}
+
+impl<E> TimingExecutor<E> {
+    pub fn new<F>(inner: E, threshold: std::time::Duration, on_slow: F) -> Self
+    where
+        F: Fn(&str, std::time::Duration) + Send + Sync + 'static,
+    {
+        Self {
+            inner,
+            threshold,
+            on_slow: Box::new(on_slow),
+        }
+    }
+
+    /// The url of the request without the key, e.g. `user/1?selections=basic`.
+    fn path<A>(request: &ApiRequest<A>, id: Option<&str>) -> String
+    where
+        A: ApiSelection,
+    {
+        format!(
+            "{}/{}?selections={}",
+            A::category(),
+            id.unwrap_or_default(),
+            join_list(request.selections.iter().copied())
+        )
+    }
+
+    fn report(&self, path: &str, start: std::time::Instant) {
+        let elapsed = start.elapsed();
+        if elapsed > self.threshold {
+            (self.on_slow)(path, elapsed);
+        }
+    }
+}
+
+/// Wraps another executor and writes the json of every successful response into `dir`, so that
+/// the session can be served offline by a [`ReplayExecutor`] later. Keys and comments are not
+/// part of the recorded requests.
+pub struct RecordingExecutor<E> {
+    inner: E,
+    dir: std::path::PathBuf,
+}
+
+impl<E> RecordingExecutor<E> {
+    pub fn new(inner: E, dir: impl Into<std::path::PathBuf>) -> Self {
+        Self {
+            inner,
+            dir: dir.into(),
+        }
+    }
+
+    fn record<A>(
+        &self,
+        request: &ApiRequest<A>,
+        id: Option<&str>,
+        value: &serde_json::Value,
+    ) -> std::io::Result<()>
+    where
+        A: ApiSelection,
+    {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(
+            fixture_path(&self.dir, request, id),
+            serde_json::to_vec_pretty(value)?,
+        )
+    }
+}
+
+/// Serves the responses recorded by a [`RecordingExecutor`] instead of sending requests, the
+/// client is never used.
+pub struct ReplayExecutor {
+    dir: std::path::PathBuf,
+}
+
+impl ReplayExecutor {
+    pub fn new(dir: impl Into<std::path::PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn replay<A>(
+        &self,
+        request: &ApiRequest<A>,
+        id: Option<&str>,
+    ) -> Result<A::Response, ApiClientError<std::io::Error>>
+    where
+        A: ApiSelection,
+    {
+        let bytes =
+            std::fs::read(fixture_path(&self.dir, request, id)).map_err(ApiClientError::Client)?;
+        let value = serde_json::from_slice(&bytes)
+            .map_err(|e| ApiClientError::Client(std::io::Error::from(e)))?;
+
+        Ok(ApiResponse::from_value(value)?.into())
+    }
+}
+
+/// The file in which the response to a request is recorded, e.g.
+/// `user_1_selections_basic,profile.json`.
+fn fixture_path<A>(
+    dir: &std::path::Path,
+    request: &ApiRequest<A>,
+    id: Option<&str>,
+) -> std::path::PathBuf
+where
+    A: ApiSelection,
+{
+    let mut selections = request.selections.clone();
+    if !request.preserve_selection_order {
+        selections.sort_unstable();
+    }
+
+    let mut name = format!(
+        "{}/{}?selections={}",
+        A::category(),
+        id.unwrap_or_default(),
+        join_list(selections.iter().copied())
+    );
+    for (key, value) in &request.query_items {
+        write!(name, "&{key}={value}").unwrap();
+    }
+
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | ',' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+
+    dir.join(name + ".json")
+}
+
+#[derive(Error, Debug)]
+pub enum RecordingError<E>
+where
+    E: std::error::Error,
+{
+    #[error(transparent)]
+    Executor(E),
+
+    #[error("failed to record response: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A string which doesn't name any selection of the category, returned when parsing selections
+/// e.g. from a config file.
+#[derive(Debug, Clone, Error)]
+#[error("unknown {category} selection `{selection}`")]
+pub struct UnknownSelection {
+    pub category: &'static str,
+    pub selection: String,
+}
+
 #[derive(Error, Debug)]
 pub enum ApiClientError<C>
 where
@@ -161,6 +590,24 @@ where
     }
 }
 
+/// Joins the items of a list parameter like `selections` with commas. Duplicates and empty items
+/// are skipped, otherwise the order is kept.
+pub(crate) fn join_list<'a>(items: impl IntoIterator<Item = &'a str>) -> String {
+    let mut joined = String::new();
+    let mut seen = Vec::new();
+    for item in items {
+        if item.is_empty() || seen.contains(&item) {
+            continue;
+        }
+        if !joined.is_empty() {
+            joined.push(',');
+        }
+        joined.push_str(item);
+        seen.push(item);
+    }
+    joined
+}
+
 #[derive(Debug)]
 pub struct ApiRequest<A>
 where
@@ -169,6 +616,9 @@ where
     pub selections: Vec<&'static str>,
     pub query_items: Vec<(&'static str, String)>,
     pub comment: Option<String>,
+    /// Selections are sorted when building the url, so that requests for the same selections
+    /// always produce the same url. Set this to keep them in the order they were added in.
+    pub preserve_selection_order: bool,
     phantom: std::marker::PhantomData<A>,
 }
 
@@ -181,6 +631,7 @@ where
             selections: Vec::default(),
             query_items: Vec::default(),
             comment: None,
+            preserve_selection_order: false,
             phantom: Default::default(),
         }
     }
@@ -205,7 +656,18 @@ where
             write!(url, "{}", id).unwrap();
         }
 
-        write!(url, "?selections={}&key={}", self.selections.join(","), key).unwrap();
+        let mut selections = self.selections.clone();
+        if !self.preserve_selection_order {
+            selections.sort_unstable();
+        }
+
+        write!(
+            url,
+            "?selections={}&key={}",
+            join_list(selections.iter().copied()),
+            key
+        )
+        .unwrap();
 
         for (name, value) in &self.query_items {
             write!(url, "&{name}={value}").unwrap();
@@ -244,16 +706,42 @@ where
     A: ApiSelection,
 {
     #[must_use]
-    pub fn selections(mut self, selections: impl IntoIterator<Item = A>) -> Self {
-        self.request.selections.append(
-            &mut selections
+    pub fn selections<I>(mut self, selections: I) -> Self
+    where
+        A: Copy,
+        I: IntoIterator,
+        I::Item: Borrow<A>,
+    {
+        self.request.selections.extend(
+            selections
                 .into_iter()
-                .map(ApiSelection::raw_value)
-                .collect(),
+                .map(|s| ApiSelection::raw_value(*s.borrow())),
         );
         self
     }
 
+    /// Adds a selection which isn't covered by this crate yet. The raw json can be accessed
+    /// through [`ApiSelectionResponse::into_inner`].
+    #[must_use]
+    pub fn raw_selection(mut self, selection: &'static str) -> Self {
+        self.request.selections.push(selection);
+        self
+    }
+
+    /// Sends the selections in the order they were added in, instead of sorting them.
+    #[must_use]
+    pub fn preserve_selection_order(mut self) -> Self {
+        self.request.preserve_selection_order = true;
+        self
+    }
+
+    /// Sets an arbitrary query parameter, replacing any previous value with the same name.
+    #[must_use]
+    pub fn query_item(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.request.add_query_item(name, value);
+        self
+    }
+
     #[must_use]
     pub fn from(mut self, from: DateTime<Utc>) -> Self {
         self.request.add_query_item("from", from.timestamp());
@@ -261,8 +749,8 @@ where
     }
 
     #[must_use]
-    pub fn from_timestamp(mut self, from: i64) -> Self {
-        self.request.add_query_item("from", from);
+    pub fn from_timestamp(mut self, from: impl IntoTimestamp) -> Self {
+        self.request.add_query_item("from", from.into_timestamp());
         self
     }
 
@@ -273,14 +761,15 @@ where
     }
 
     #[must_use]
-    pub fn to_timestamp(mut self, to: i64) -> Self {
-        self.request.add_query_item("to", to);
+    pub fn to_timestamp(mut self, to: impl IntoTimestamp) -> Self {
+        self.request.add_query_item("to", to.into_timestamp());
         self
     }
 
     #[must_use]
-    pub fn stats_timestamp(mut self, ts: i64) -> Self {
-        self.request.add_query_item("timestamp", ts);
+    pub fn stats_timestamp(mut self, ts: impl IntoTimestamp) -> Self {
+        self.request
+            .add_query_item("timestamp", ts.into_timestamp());
         self
     }
 
@@ -343,6 +832,29 @@ pub(crate) mod tests {
         assert_eq!(user::Selection::Basic.raw_value(), "basic");
     }
 
+    #[cfg(all(feature = "user", feature = "faction"))]
+    #[test]
+    fn selection_from_str() {
+        for selection in [
+            user::Selection::Basic,
+            user::Selection::PersonalStats,
+            user::Selection::Discord,
+        ] {
+            let parsed: user::Selection = selection.to_string().parse().unwrap();
+            assert_eq!(parsed.raw_value(), selection.raw_value());
+        }
+
+        assert_eq!(user::Selection::PersonalStats.to_string(), "personalstats");
+        assert!(matches!(
+            "Basic".parse::<faction::Selection>(),
+            Ok(faction::Selection::Basic)
+        ));
+
+        let err = "attack".parse::<faction::Selection>().unwrap_err();
+        assert_eq!(err.category, "faction");
+        assert_eq!(err.selection, "attack");
+    }
+
     #[cfg(all(feature = "reqwest", feature = "user"))]
     #[tokio::test]
     async fn reqwest() {
@@ -359,6 +871,516 @@ pub(crate) mod tests {
         Client::default().torn_api(key).user(|b| b).await.unwrap();
     }
 
+    #[test]
+    fn url_builder_selections() {
+        let expected = "https://api.torn.com/user/?selections=basic,profile&key=";
+        let selections = vec![user::Selection::Basic, user::Selection::Profile];
+
+        let url = ApiRequestBuilder::<user::Selection>::default()
+            .selections(selections.clone())
+            .request
+            .url("", None);
+        assert_eq!(expected, url);
+
+        let url = ApiRequestBuilder::<user::Selection>::default()
+            .selections([user::Selection::Basic, user::Selection::Profile])
+            .request
+            .url("", None);
+        assert_eq!(expected, url);
+
+        let url = ApiRequestBuilder::<user::Selection>::default()
+            .selections(selections.as_slice())
+            .request
+            .url("", None);
+        assert_eq!(expected, url);
+    }
+
+    #[cfg(feature = "user")]
+    #[test]
+    fn timing_executor() {
+        use std::sync::{Arc, Mutex};
+
+        struct SlowClient;
+
+        #[async_trait::async_trait]
+        impl send::ApiClient for SlowClient {
+            type Error = std::convert::Infallible;
+
+            async fn request(&self, _url: String) -> Result<serde_json::Value, Self::Error> {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                Ok(serde_json::json!({ "player_id": 1 }))
+            }
+        }
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let timed = |threshold| {
+            let reports = reports.clone();
+            TimingExecutor::new(
+                DirectExecutor::new("".to_owned()),
+                std::time::Duration::from_millis(threshold),
+                move |path, _| reports.lock().unwrap().push(path.to_owned()),
+            )
+        };
+
+        futures::executor::block_on(async {
+            send::ApiProvider::new(&SlowClient, timed(10))
+                .user(|b| b.id(1).selections([user::Selection::Basic]))
+                .await
+                .unwrap();
+            send::ApiProvider::new(&SlowClient, timed(1000))
+                .user(|b| b)
+                .await
+                .unwrap();
+        });
+
+        assert_eq!(*reports.lock().unwrap(), ["user/1?selections=basic"]);
+    }
+
+    #[cfg(feature = "user")]
+    #[test]
+    fn rotating_executor() {
+        use send::ApiClient;
+        use std::sync::Mutex;
+
+        /// Rate limits every key starting with `limited`.
+        struct LimitedClient(Mutex<Vec<String>>);
+
+        #[async_trait::async_trait]
+        impl send::ApiClient for LimitedClient {
+            type Error = std::convert::Infallible;
+
+            async fn request(&self, url: String) -> Result<serde_json::Value, Self::Error> {
+                let key = url.split("key=").nth(1).unwrap().to_owned();
+                let limited = key.starts_with("limited");
+                self.0.lock().unwrap().push(key);
+                if limited {
+                    Ok(serde_json::json!({ "error": { "code": 5, "error": "Too many requests" } }))
+                } else {
+                    Ok(serde_json::json!({ "player_id": 1 }))
+                }
+            }
+        }
+
+        let client = LimitedClient(Default::default());
+        futures::executor::block_on(async {
+            let provider = client.torn_api_rotating(["a", "limited", "b"]);
+            for _ in 0..4 {
+                provider.user(|b| b).await.unwrap();
+            }
+
+            let provider = client.torn_api_rotating(["limited1", "limited2"]);
+            let response = provider.users([1, 2], |b| b).await;
+            assert!(response
+                .values()
+                .all(|r| r.as_ref().err().and_then(ApiClientError::api_code) == Some(5)));
+        });
+
+        assert_eq!(
+            *client.0.lock().unwrap(),
+            ["a", "limited", "b", "a", "b", "limited1", "limited2"]
+        );
+    }
+
+    #[cfg(feature = "faction")]
+    #[test]
+    fn faction_attacks_history() {
+        use futures::StreamExt;
+        use send::ApiClient;
+        use std::sync::Mutex;
+
+        /// Serves two pages of attacks, split at the `to` parameter.
+        struct HistoryClient(Mutex<Vec<String>>);
+
+        #[async_trait::async_trait]
+        impl send::ApiClient for HistoryClient {
+            type Error = std::convert::Infallible;
+
+            async fn request(&self, url: String) -> Result<serde_json::Value, Self::Error> {
+                let attack = |started: i64| {
+                    serde_json::json!({
+                        "code": "abc",
+                        "timestamp_started": started,
+                        "timestamp_ended": started + 10,
+                        "attacker_id": 1,
+                        "attacker_name": "attacker",
+                        "attacker_faction": 1,
+                        "attacker_factionname": "faction",
+                        "defender_id": 2,
+                        "defender_name": "defender",
+                        "defender_faction": "",
+                        "defender_factionname": "",
+                        "result": "Hospitalized",
+                        "stealthed": 0,
+                        "raid": 0,
+                        "ranked_war": 0,
+                        "respect": 1.5,
+                        "respect_loss": 0,
+                        "modifiers": {
+                            "fair_fight": 1,
+                            "war": 1,
+                            "retaliation": 1,
+                            "group_attack": 1,
+                            "overseas": 1,
+                            "chain_bonus": 1
+                        }
+                    })
+                };
+
+                let to = url
+                    .split('&')
+                    .find_map(|item| item.strip_prefix("to="))
+                    .map(|to| to.to_owned());
+                self.0.lock().unwrap().push(to.clone().unwrap_or_default());
+
+                Ok(match to.as_deref() {
+                    None => {
+                        serde_json::json!({ "attacks": { "3": attack(300), "2": attack(200) } })
+                    }
+                    Some("200") => serde_json::json!({ "attacks": { "1": attack(100) } }),
+                    Some(_) => serde_json::json!({ "attacks": [] }),
+                })
+            }
+        }
+
+        let client = HistoryClient(Default::default());
+        let pages = futures::executor::block_on(
+            client
+                .torn_api("")
+                .faction_attacks_history(|b| b.selections([faction::Selection::Attacks]))
+                .collect::<Vec<_>>(),
+        );
+
+        assert_eq!(pages.len(), 2);
+        let ids: Vec<_> = pages
+            .iter()
+            .flat_map(|page| page.as_ref().unwrap().attacks().unwrap().into_keys())
+            .collect();
+        assert_eq!(ids, [2, 3, 1]);
+        assert_eq!(*client.0.lock().unwrap(), ["", "200", "100"]);
+
+        struct MalformedClient;
+
+        #[async_trait::async_trait]
+        impl send::ApiClient for MalformedClient {
+            type Error = std::convert::Infallible;
+
+            async fn request(&self, _url: String) -> Result<serde_json::Value, Self::Error> {
+                Ok(serde_json::json!({ "attacks": { "1": { "code": 1 } } }))
+            }
+        }
+
+        let pages = futures::executor::block_on(
+            MalformedClient
+                .torn_api("")
+                .faction_attacks_history(|b| b)
+                .collect::<Vec<_>>(),
+        );
+        assert!(matches!(
+            pages.as_slice(),
+            [Err(ApiClientError::Response(
+                ResponseError::MalformedResponse(_)
+            ))]
+        ));
+    }
+
+    #[cfg(feature = "user")]
+    #[test]
+    fn record_and_replay() {
+        struct StaticClient;
+
+        #[async_trait::async_trait]
+        impl send::ApiClient for StaticClient {
+            type Error = std::convert::Infallible;
+
+            async fn request(&self, url: String) -> Result<serde_json::Value, Self::Error> {
+                assert!(url.contains("key=secret"));
+                Ok(serde_json::json!({ "player_id": 1, "level": 15 }))
+            }
+        }
+
+        let dir = std::env::temp_dir().join(format!("torn-api-replay-{}", std::process::id()));
+        let request = |b: ApiRequestBuilder<user::Selection>| {
+            b.id(1)
+                .selections([user::Selection::Profile, user::Selection::Basic])
+                .query_item("limit", 10)
+        };
+
+        futures::executor::block_on(async {
+            let recorded = send::ApiProvider::new(
+                &StaticClient,
+                RecordingExecutor::new(DirectExecutor::new("secret".to_owned()), &dir),
+            )
+            .user(request)
+            .await
+            .unwrap();
+
+            let replayed = send::ApiProvider::new(&StaticClient, ReplayExecutor::new(&dir))
+                .user(request)
+                .await
+                .unwrap();
+            assert_eq!(replayed.raw_json(), recorded.raw_json());
+
+            let missing = send::ApiProvider::new(&StaticClient, ReplayExecutor::new(&dir))
+                .user(|b| b.id(2))
+                .await;
+            assert!(matches!(missing, Err(ApiClientError::Client(_))));
+        });
+
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["user_1_selections_basic,profile_limit_10.json"]);
+        assert!(!std::fs::read_to_string(dir.join(&files[0]))
+            .unwrap()
+            .contains("secret"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn flexible_bools() {
+        #[derive(Deserialize)]
+        struct Flag(#[serde(deserialize_with = "de_util::flexible_bool")] bool);
+
+        for (value, expected) in [
+            (serde_json::json!(true), true),
+            (serde_json::json!(0), false),
+            (serde_json::json!(1), true),
+            (serde_json::json!("0"), false),
+            (serde_json::json!("1"), true),
+            (serde_json::json!("false"), false),
+            (serde_json::json!("yes"), true),
+            (serde_json::json!("No"), false),
+        ] {
+            let Flag(flag) = serde_json::from_value(value).unwrap();
+            assert_eq!(flag, expected);
+        }
+
+        assert!(serde_json::from_value::<Flag>(serde_json::json!(2)).is_err());
+        assert!(serde_json::from_value::<Flag>(serde_json::json!("maybe")).is_err());
+    }
+
+    #[test]
+    fn empty_maps() {
+        #[derive(Deserialize)]
+        struct Maps {
+            #[serde(deserialize_with = "de_util::empty_map")]
+            hash: std::collections::HashMap<String, i32>,
+            #[serde(deserialize_with = "de_util::empty_map")]
+            btree: std::collections::BTreeMap<i32, i32>,
+        }
+
+        for empty in [
+            serde_json::json!({}),
+            serde_json::json!([]),
+            serde_json::Value::Null,
+        ] {
+            let maps: Maps = serde_json::from_value(serde_json::json!({
+                "hash": empty,
+                "btree": empty,
+            }))
+            .unwrap();
+            assert!(maps.hash.is_empty());
+            assert!(maps.btree.is_empty());
+        }
+
+        let maps: Maps = serde_json::from_value(serde_json::json!({
+            "hash": { "a": 1 },
+            "btree": { "2": 3 },
+        }))
+        .unwrap();
+        assert_eq!(maps.hash["a"], 1);
+        assert_eq!(maps.btree[&2], 3);
+
+        assert!(serde_json::from_value::<Maps>(serde_json::json!({
+            "hash": [1],
+            "btree": {},
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn list_joining() {
+        assert_eq!(join_list([]), "");
+        assert_eq!(join_list(["basic"]), "basic");
+        assert_eq!(
+            join_list(["basic", "", "profile", "basic"]),
+            "basic,profile"
+        );
+        assert_eq!(join_list(["profile", "basic"]), "profile,basic");
+    }
+
+    #[test]
+    fn selection_order() {
+        let builder = ApiRequestBuilder::<user::Selection>::default()
+            .selections([user::Selection::Profile, user::Selection::Basic]);
+        assert_eq!(
+            builder.request.url("", None),
+            "https://api.torn.com/user/?selections=basic,profile&key="
+        );
+
+        let builder = builder.preserve_selection_order();
+        assert_eq!(
+            builder.request.url("", None),
+            "https://api.torn.com/user/?selections=profile,basic&key="
+        );
+    }
+
+    #[test]
+    fn url_builder_raw() {
+        let url = ApiRequestBuilder::<user::Selection>::default()
+            .selections([user::Selection::Basic])
+            .raw_selection("newselection")
+            .query_item("limit", 10)
+            .query_item("limit", 20)
+            .request
+            .url("", None);
+
+        assert_eq!(
+            "https://api.torn.com/user/?selections=basic,newselection&key=&limit=20",
+            url
+        );
+    }
+
+    #[test]
+    fn url_builder_id_list() {
+        let builder =
+            ApiRequestBuilder::<torn::Selection>::default().id(IdList::from(vec![1, 2, 3]));
+        let url = builder.request.url("", builder.id.as_deref());
+
+        assert_eq!("https://api.torn.com/torn/1,2,3?selections=&key=", url);
+
+        assert_eq!(IdList::from([1, 2, 3]).to_string(), "1,2,3");
+        assert_eq!(IdList::from(&["NSC", "AAA"][..]).to_string(), "NSC,AAA");
+        assert_eq!(IdList::<i32>::default().to_string(), "");
+    }
+
+    #[test]
+    fn error_message() {
+        let with_reason = ApiResponse::from_value(serde_json::json!({
+            "error": { "code": 2, "error": "Incorrect key", "details": "ignored" }
+        }))
+        .err()
+        .unwrap();
+        assert_eq!(with_reason.message(), Some("Incorrect key"));
+
+        let without_reason =
+            ApiResponse::from_value(serde_json::json!({ "error": { "code": 16 } }))
+                .err()
+                .unwrap();
+        assert_eq!(without_reason.api_code(), Some(16));
+        assert_eq!(
+            without_reason.message(),
+            Some("Access level of this key is not high enough")
+        );
+
+        assert_eq!(ResponseError::EmptyResponse.message(), None);
+    }
+
+    #[test]
+    fn custom_error_codes() {
+        const CUSTOM: &[(u8, &str)] = &[(42, "Custom error")];
+
+        let description = API_ERROR_CODES
+            .iter()
+            .chain(CUSTOM)
+            .find(|(code, _)| *code == 42)
+            .map(|(_, description)| *description);
+
+        assert_eq!(description, Some("Custom error"));
+        assert_eq!(api_error_description(42), "Unknown error");
+        assert_eq!(api_error_description(5), "Too many requests");
+    }
+
+    #[cfg(feature = "user")]
+    #[test]
+    fn missing_selection() {
+        let status = serde_json::json!({
+            "description": "Okay",
+            "details": "",
+            "state": "Okay",
+            "color": "green",
+            "until": 0
+        });
+
+        // the basic fields are part of the profile
+        let response = user::Response::from(
+            ApiResponse::from_value(serde_json::json!({
+                "player_id": 1,
+                "name": "Chedburn",
+                "level": 15,
+                "gender": "Male",
+                "status": status,
+                "rank": "Legendary"
+            }))
+            .unwrap(),
+        );
+        assert_eq!(response.basic().unwrap().name, "Chedburn");
+
+        let response = user::Response::from(
+            ApiResponse::from_value(serde_json::json!({ "level": 1 })).unwrap(),
+        );
+        let err = response.basic().unwrap_err().to_string();
+        assert!(err.starts_with("selection `basic` is missing from the response"));
+
+        let err = response.personal_stats().unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "selection `personalstats` is missing from the response (missing field `personalstats`)"
+        );
+    }
+
+    #[cfg(feature = "user")]
+    #[test]
+    fn raw_json() {
+        let response = user::Response::from(
+            ApiResponse::from_value(serde_json::json!({ "level": 1, "new_field": "value" }))
+                .unwrap(),
+        );
+
+        assert_eq!(response.raw_json()["new_field"], "value");
+    }
+
+    #[cfg(feature = "user")]
+    #[test]
+    fn malformed_response_snippet() {
+        let response = user::Response::from(
+            ApiResponse::from_value(serde_json::json!({
+                "player_id": "one",
+                "personalstats": { "attackswon": "many" }
+            }))
+            .unwrap(),
+        );
+
+        let why = ResponseError::from(response.personal_stats().unwrap_err());
+        assert!(matches!(why, ResponseError::MalformedResponse(_)));
+        assert!(why
+            .to_string()
+            .ends_with(r#"in `personalstats`: {"attackswon":"many"}"#));
+
+        let why = response.basic().unwrap_err();
+        assert!(why.to_string().contains(r#""player_id":"one""#));
+    }
+
+    #[test]
+    fn empty_response() {
+        assert!(matches!(
+            ApiResponse::from_value(serde_json::Value::Null),
+            Err(ResponseError::EmptyResponse)
+        ));
+    }
+
+    #[test]
+    fn truncated_body_snippet() {
+        assert_eq!(body_snippet(b"<html>"), "<html>");
+
+        let long = "ä".repeat(200);
+        let snippet = body_snippet(long.as_bytes());
+        assert!(snippet.ends_with("..."));
+        assert_eq!(snippet.len(), 256 + 3);
+    }
+
     #[test]
     fn url_builder_from_dt() {
         let url = ApiRequestBuilder::<user::Selection>::default()
@@ -402,6 +1424,20 @@ pub(crate) mod tests {
         assert_eq!("https://api.torn.com/user/?selections=&key=&to=12345", url);
     }
 
+    #[test]
+    fn url_builder_ts_from_dt() {
+        let url = ApiRequestBuilder::<user::Selection>::default()
+            .from_timestamp(DateTime::from_timestamp(12345, 0).unwrap())
+            .to_timestamp(DateTime::from_timestamp(67890, 0).unwrap())
+            .request
+            .url("", None);
+
+        assert_eq!(
+            "https://api.torn.com/user/?selections=&key=&from=12345&to=67890",
+            url
+        );
+    }
+
     #[test]
     fn url_builder_timestamp_dt() {
         let url = ApiRequestBuilder::<user::Selection>::default()
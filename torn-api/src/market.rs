@@ -1,16 +1,33 @@
 use serde::Deserialize;
 use torn_api_macros::ApiCategory;
 
+use crate::de_util::unix_timestamp;
+
 #[derive(Debug, Clone, Copy, ApiCategory)]
 #[api(category = "market")]
 pub enum MarketSelection {
     #[api(type = "Vec<BazaarItem>", field = "bazaar")]
     Bazaar,
+
+    #[api(
+        type = "chrono::DateTime<chrono::Utc>",
+        field = "timestamp",
+        with = "unix_timestamp"
+    )]
+    Timestamp,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct BazaarItem {
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "crate::de_util::flexible_number")
+    )]
     pub cost: u64,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "crate::de_util::flexible_number")
+    )]
     pub quantity: u32,
 }
 
@@ -19,6 +36,16 @@ mod test {
     use super::*;
     use crate::tests::{async_test, setup, Client, ClientTrait};
 
+    #[cfg(feature = "lenient")]
+    #[test]
+    fn lenient_numbers() {
+        let item: BazaarItem =
+            serde_json::from_value(serde_json::json!({ "cost": "1500", "quantity": 2 })).unwrap();
+
+        assert_eq!(item.cost, 1500);
+        assert_eq!(item.quantity, 2);
+    }
+
     #[async_test]
     async fn market_bazaar() {
         let key = setup();
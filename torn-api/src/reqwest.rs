@@ -1,12 +1,235 @@
 use async_trait::async_trait;
+use thiserror::Error;
 
-use crate::send::ApiClient;
+use crate::ApiClientError;
 
+#[derive(Error, Debug)]
+pub enum ReqwestApiClientError {
+    #[error(transparent)]
+    Client(#[from] reqwest::Error),
+
+    #[error("Malformed response body ({source}): {body_snippet}")]
+    Deserialize {
+        #[source]
+        source: serde_json::Error,
+        body_snippet: String,
+    },
+}
+
+impl ReqwestApiClientError {
+    /// Whether the request timed out. Timeouts are usually safe to retry.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Self::Client(err) if err.is_timeout())
+    }
+
+    /// Whether the connection to the API could not be established.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn is_connect(&self) -> bool {
+        matches!(self, Self::Client(err) if err.is_connect())
+    }
+}
+
+impl ApiClientError<ReqwestApiClientError> {
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Self::Client(err) if err.is_timeout())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn is_connect(&self) -> bool {
+        matches!(self, Self::Client(err) if err.is_connect())
+    }
+}
+
+async fn request(
+    client: &reqwest::Client,
+    url: String,
+) -> Result<serde_json::Value, ReqwestApiClientError> {
+    let body = client.get(url).send().await?.bytes().await?;
+    if body.is_empty() {
+        return Ok(serde_json::Value::Null);
+    }
+
+    serde_json::from_slice(&body).map_err(|source| ReqwestApiClientError::Deserialize {
+        source,
+        body_snippet: crate::body_snippet(&body),
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 #[async_trait]
-impl ApiClient for reqwest::Client {
-    type Error = reqwest::Error;
+impl crate::send::ApiClient for reqwest::Client {
+    type Error = ReqwestApiClientError;
+
+    async fn request(&self, url: String) -> Result<serde_json::Value, Self::Error> {
+        request(self, url).await
+    }
+}
+
+// the futures of reqwest's fetch based backend aren't `Send`
+#[cfg(target_arch = "wasm32")]
+#[async_trait(?Send)]
+impl crate::local::ApiClient for reqwest::Client {
+    type Error = ReqwestApiClientError;
 
     async fn request(&self, url: String) -> Result<serde_json::Value, Self::Error> {
-        self.get(url).send().await?.json().await
+        request(self, url).await
+    }
+}
+
+/// Result of a conditional request.
+#[cfg(feature = "conditional")]
+#[derive(Debug, Clone)]
+pub enum Conditional {
+    Modified(serde_json::Value),
+    NotModified,
+}
+
+/// A client which remembers the `ETag` of each requested url and sends it back in an
+/// `If-None-Match` header. If the server answers with `304 Not Modified`, the previously returned
+/// body is used.
+///
+/// Entries are never evicted, so this is meant for a bounded set of urls, e.g. when polling the
+/// same selections through a caching proxy.
+#[cfg(feature = "conditional")]
+#[derive(Debug, Default)]
+pub struct ConditionalClient {
+    pub client: reqwest::Client,
+    cache: std::sync::Mutex<std::collections::HashMap<String, (String, serde_json::Value)>>,
+}
+
+#[cfg(feature = "conditional")]
+impl ConditionalClient {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            cache: Default::default(),
+        }
+    }
+
+    pub async fn request_conditional(
+        &self,
+        url: String,
+    ) -> Result<Conditional, ReqwestApiClientError> {
+        let etag = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(&url)
+            .map(|(etag, _)| etag.clone());
+
+        let mut builder = self.client.get(&url);
+        if let Some(etag) = etag {
+            builder = builder.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+
+        let response = builder.send().await?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(Conditional::NotModified);
+        }
+
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(ToOwned::to_owned);
+
+        let body = response.bytes().await?;
+        if body.is_empty() {
+            return Ok(Conditional::Modified(serde_json::Value::Null));
+        }
+
+        let value: serde_json::Value =
+            serde_json::from_slice(&body).map_err(|source| ReqwestApiClientError::Deserialize {
+                source,
+                body_snippet: crate::body_snippet(&body),
+            })?;
+
+        if let Some(etag) = etag {
+            self.cache
+                .lock()
+                .unwrap()
+                .insert(url, (etag, value.clone()));
+        }
+
+        Ok(Conditional::Modified(value))
+    }
+}
+
+#[cfg(all(feature = "conditional", not(target_arch = "wasm32")))]
+#[async_trait]
+impl crate::send::ApiClient for ConditionalClient {
+    type Error = ReqwestApiClientError;
+
+    async fn request(&self, url: String) -> Result<serde_json::Value, Self::Error> {
+        match self.request_conditional(url.clone()).await? {
+            Conditional::Modified(value) => Ok(value),
+            Conditional::NotModified => Ok(self
+                .cache
+                .lock()
+                .unwrap()
+                .get(&url)
+                .map(|(_, value)| value.clone())
+                .unwrap_or_default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::send::ApiClient;
+
+    #[tokio::test]
+    async fn connect_error() {
+        let err = ApiClient::request(&reqwest::Client::new(), "http://127.0.0.1:1/".to_owned())
+            .await
+            .unwrap_err();
+
+        assert!(err.is_connect());
+        assert!(!err.is_timeout());
+    }
+
+    #[cfg(feature = "conditional")]
+    #[tokio::test]
+    async fn conditional_request() {
+        use std::io::{BufRead, BufReader, Write};
+
+        use super::{Conditional, ConditionalClient};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            for (idx, stream) in listener.incoming().take(2).enumerate() {
+                let mut stream = stream.unwrap();
+                let mut conditional = false;
+                for line in BufReader::new(&stream).lines() {
+                    let line = line.unwrap();
+                    if line.is_empty() {
+                        break;
+                    }
+                    conditional |= line.to_lowercase() == "if-none-match: \"abc\"";
+                }
+
+                let response = if conditional {
+                    "HTTP/1.1 304 Not Modified\r\nconnection: close\r\n\r\n".to_owned()
+                } else {
+                    let body = format!(r#"{{"level":{}}}"#, idx + 1);
+                    format!(
+                        "HTTP/1.1 200 OK\r\netag: \"abc\"\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let client = ConditionalClient::default();
+        assert!(matches!(
+            client.request_conditional(url.clone()).await.unwrap(),
+            Conditional::Modified(_)
+        ));
+
+        let value = client.request(url).await.unwrap();
+        assert_eq!(value, serde_json::json!({ "level": 1 }));
     }
 }
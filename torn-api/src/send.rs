@@ -2,14 +2,38 @@ use std::collections::HashMap;
 
 use async_trait::async_trait;
 
-use crate::{ApiClientError, ApiRequest, ApiResponse, ApiSelection, DirectExecutor};
+use crate::{
+    ApiClientError, ApiRequest, ApiResponse, ApiSelection, ApiSelectionResponse, DirectExecutor,
+    RecordingError, RecordingExecutor, ReplayExecutor, ResponseError, RotatingExecutor,
+    TimingExecutor,
+};
 
 pub struct ApiProvider<'a, C, E>
 where
     C: ApiClient,
     E: RequestExecutor<C>,
 {
+    #[cfg_attr(
+        not(any(
+            feature = "user",
+            feature = "faction",
+            feature = "torn",
+            feature = "key",
+            feature = "market"
+        )),
+        allow(dead_code)
+    )]
     client: &'a C,
+    #[cfg_attr(
+        not(any(
+            feature = "user",
+            feature = "faction",
+            feature = "torn",
+            feature = "key",
+            feature = "market"
+        )),
+        allow(dead_code)
+    )]
     executor: E,
 }
 
@@ -37,6 +61,83 @@ where
             .await
     }
 
+    /// Fetches the `basic` selection of a user, or of the key owner if `id` is `None`.
+    #[cfg(feature = "user")]
+    pub async fn user_basic(&self, id: Option<i32>) -> Result<crate::user::BasicOwned, E::Error>
+    where
+        E::Error: crate::FromResponseError,
+    {
+        use crate::{FromResponseError, IntoOwned};
+
+        let response = self
+            .user(|b| {
+                let b = b.selections([crate::user::Selection::Basic]);
+                match id {
+                    Some(id) => b.id(id),
+                    None => b,
+                }
+            })
+            .await?;
+
+        response
+            .basic()
+            .map(IntoOwned::into_owned)
+            .map_err(|e| E::Error::from_response_error(e.into()))
+    }
+
+    /// Fetches the `profile` selection of a user, or of the key owner if `id` is `None`.
+    #[cfg(feature = "user")]
+    pub async fn user_profile(&self, id: Option<i32>) -> Result<crate::user::ProfileOwned, E::Error>
+    where
+        E::Error: crate::FromResponseError,
+    {
+        use crate::{FromResponseError, IntoOwned};
+
+        let response = self
+            .user(|b| {
+                let b = b.selections([crate::user::Selection::Profile]);
+                match id {
+                    Some(id) => b.id(id),
+                    None => b,
+                }
+            })
+            .await?;
+
+        response
+            .profile()
+            .map(IntoOwned::into_owned)
+            .map_err(|e| E::Error::from_response_error(e.into()))
+    }
+
+    /// Fetches the key owner's events which are newer than `cursor`, oldest first, and moves
+    /// the cursor past them.
+    #[cfg(feature = "user")]
+    pub async fn poll_events(
+        &self,
+        cursor: &mut crate::user::EventCursor,
+    ) -> Result<Vec<(String, crate::user::Event)>, E::Error>
+    where
+        E::Error: crate::FromResponseError,
+    {
+        use crate::FromResponseError;
+
+        let response = self
+            .user(|b| {
+                let b = b.selections([crate::user::Selection::Events]);
+                match cursor.timestamp {
+                    Some(timestamp) => b.from(timestamp),
+                    None => b,
+                }
+            })
+            .await?;
+
+        let events = response
+            .events()
+            .map_err(|e| E::Error::from_response_error(e.into()))?;
+
+        Ok(cursor.advance(events))
+    }
+
     #[cfg(feature = "user")]
     pub async fn users<F, L, I>(
         &self,
@@ -73,6 +174,82 @@ where
             .await
     }
 
+    /// Fetches the members of a faction, or of the key owner's faction if `id` is `None`.
+    #[cfg(feature = "faction")]
+    pub async fn faction_members(
+        &self,
+        id: Option<i32>,
+    ) -> Result<std::collections::BTreeMap<i32, crate::faction::MemberOwned>, E::Error>
+    where
+        E::Error: crate::FromResponseError,
+    {
+        use crate::{FromResponseError, IntoOwned};
+
+        let response = self
+            .faction(|b| {
+                let b = b.selections([crate::faction::Selection::Basic]);
+                match id {
+                    Some(id) => b.id(id),
+                    None => b,
+                }
+            })
+            .await?;
+
+        response
+            .members_map()
+            .map(IntoOwned::into_owned)
+            .map_err(|e| E::Error::from_response_error(e.into()))
+    }
+
+    /// Fetches the members of a faction like [`Self::faction_members`], and then the `basic`
+    /// selection of every member in one bulk request. Members for which the bulk request
+    /// returned no result get a [`crate::ResponseError::MissingBulkResponse`].
+    #[cfg(all(feature = "faction", feature = "user"))]
+    #[allow(clippy::type_complexity)]
+    pub async fn faction_members_with_user_basic(
+        &self,
+        id: Option<i32>,
+    ) -> Result<
+        std::collections::BTreeMap<
+            i32,
+            (
+                crate::faction::MemberOwned,
+                Result<crate::user::BasicOwned, E::Error>,
+            ),
+        >,
+        E::Error,
+    >
+    where
+        E::Error: crate::FromResponseError,
+    {
+        use crate::{FromResponseError, IntoOwned};
+
+        let members = self.faction_members(id).await?;
+        let mut responses = self
+            .users(members.keys().copied(), |b| {
+                b.selections([crate::user::Selection::Basic])
+            })
+            .await;
+
+        Ok(members
+            .into_iter()
+            .map(|(id, member)| {
+                let basic = match responses.remove(&id) {
+                    Some(response) => response.and_then(|response| {
+                        response
+                            .basic()
+                            .map(IntoOwned::into_owned)
+                            .map_err(|e| E::Error::from_response_error(e.into()))
+                    }),
+                    None => Err(E::Error::from_response_error(
+                        crate::ResponseError::MissingBulkResponse { id: id.to_string() },
+                    )),
+                };
+                (id, (member, basic))
+            })
+            .collect())
+    }
+
     #[cfg(feature = "faction")]
     pub async fn factions<F, L, I>(
         &self,
@@ -94,6 +271,79 @@ where
             .await
     }
 
+    #[cfg(feature = "faction")]
+    pub async fn faction_attacks_since<F>(
+        &self,
+        from: impl crate::IntoTimestamp,
+        build: F,
+    ) -> Result<crate::faction::Response, E::Error>
+    where
+        F: FnOnce(
+            crate::ApiRequestBuilder<crate::faction::Selection>,
+        ) -> crate::ApiRequestBuilder<crate::faction::Selection>,
+    {
+        self.faction(|b| build(b).from_timestamp(from)).await
+    }
+
+    /// Walks backwards through a faction's attack log, one page at a time.
+    ///
+    /// Every page after the first is requested with `to` set to the oldest `timestamp_started`
+    /// of the previous page, so attacks on the page boundary are returned twice and should be
+    /// deduplicated by their id. If `build` selects neither `attacks` nor `attacksfull`,
+    /// `attacksfull` is added. The stream ends on the first empty page, the first error, or when
+    /// a page no longer moves the window back. Pages whose attacks can't be decoded end the
+    /// stream with an error.
+    #[cfg(feature = "faction")]
+    pub fn faction_attacks_history<F>(
+        &self,
+        build: F,
+    ) -> impl futures::Stream<Item = Result<crate::faction::Response, E::Error>> + '_
+    where
+        F: Fn(
+                crate::ApiRequestBuilder<crate::faction::Selection>,
+            ) -> crate::ApiRequestBuilder<crate::faction::Selection>
+            + 'a,
+        E::Error: crate::FromResponseError,
+    {
+        use crate::faction::Selection;
+
+        crate::faction::history(
+            build,
+            [Selection::AttacksFull, Selection::Attacks],
+            move |builder| {
+                self.executor
+                    .execute(self.client, builder.request, builder.id)
+            },
+        )
+    }
+
+    /// Walks backwards through a faction's revive log like [`Self::faction_attacks_history`],
+    /// using the `timestamp` of the revives. `revivesfull` is added if `build` selects neither
+    /// `revives` nor `revivesfull`.
+    #[cfg(feature = "faction")]
+    pub fn faction_revives_history<F>(
+        &self,
+        build: F,
+    ) -> impl futures::Stream<Item = Result<crate::faction::Response, E::Error>> + '_
+    where
+        F: Fn(
+                crate::ApiRequestBuilder<crate::faction::Selection>,
+            ) -> crate::ApiRequestBuilder<crate::faction::Selection>
+            + 'a,
+        E::Error: crate::FromResponseError,
+    {
+        use crate::faction::Selection;
+
+        crate::faction::history(
+            build,
+            [Selection::RevivesFull, Selection::Revives],
+            move |builder| {
+                self.executor
+                    .execute(self.client, builder.request, builder.id)
+            },
+        )
+    }
+
     #[cfg(feature = "market")]
     pub async fn market<F>(&self, build: F) -> Result<crate::market::Response, E::Error>
     where
@@ -145,6 +395,21 @@ where
             .await
     }
 
+    /// Fetches the current time of the API server.
+    #[cfg(feature = "torn")]
+    pub async fn server_time(&self) -> Result<crate::ServerTime, E::Error>
+    where
+        E::Error: crate::FromResponseError,
+    {
+        use crate::FromResponseError;
+
+        self.torn(|b| b.selections([crate::torn::Selection::Timestamp]))
+            .await?
+            .timestamp()
+            .map(crate::ServerTime)
+            .map_err(|e| E::Error::from_response_error(e.into()))
+    }
+
     #[cfg(feature = "torn")]
     pub async fn torns<F, L, I>(
         &self,
@@ -264,17 +529,261 @@ where
     }
 }
 
+#[async_trait]
+impl<C> RequestExecutor<C> for RotatingExecutor<C>
+where
+    C: ApiClient,
+{
+    type Error = ApiClientError<C::Error>;
+
+    async fn execute<A>(
+        &self,
+        client: &C,
+        request: ApiRequest<A>,
+        id: Option<String>,
+    ) -> Result<A::Response, Self::Error>
+    where
+        A: ApiSelection,
+    {
+        rotate(self, client, &request, id.as_deref()).await
+    }
+
+    async fn execute_many<A, I>(
+        &self,
+        client: &C,
+        request: ApiRequest<A>,
+        ids: Vec<I>,
+    ) -> HashMap<I, Result<A::Response, Self::Error>>
+    where
+        A: ApiSelection,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+    {
+        let request_ref = &request;
+        let tuples = futures::future::join_all(ids.into_iter().map(|i| async move {
+            let id_string = i.to_string();
+            (i, rotate(self, client, request_ref, Some(&id_string)).await)
+        }))
+        .await;
+
+        HashMap::from_iter(tuples)
+    }
+}
+
+async fn rotate<C, A>(
+    executor: &RotatingExecutor<C>,
+    client: &C,
+    request: &ApiRequest<A>,
+    id: Option<&str>,
+) -> Result<A::Response, ApiClientError<C::Error>>
+where
+    C: ApiClient,
+    A: ApiSelection,
+{
+    loop {
+        let key = executor.next_key()?;
+        let url = request.url(key, id);
+        let value = client.request(url).await.map_err(ApiClientError::Client)?;
+
+        match ApiResponse::from_value(value) {
+            Err(ResponseError::Api { code: 5, .. }) => executor.rate_limited(key),
+            response => return Ok(response?.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl<C, E> RequestExecutor<C> for TimingExecutor<E>
+where
+    C: ApiClient,
+    E: RequestExecutor<C> + Sync,
+{
+    type Error = E::Error;
+
+    async fn execute<A>(
+        &self,
+        client: &C,
+        request: ApiRequest<A>,
+        id: Option<String>,
+    ) -> Result<A::Response, Self::Error>
+    where
+        A: ApiSelection,
+    {
+        let path = Self::path(&request, id.as_deref());
+        let start = std::time::Instant::now();
+        let result = self.inner.execute(client, request, id).await;
+        self.report(&path, start);
+        result
+    }
+
+    async fn execute_many<A, I>(
+        &self,
+        client: &C,
+        request: ApiRequest<A>,
+        ids: Vec<I>,
+    ) -> HashMap<I, Result<A::Response, Self::Error>>
+    where
+        A: ApiSelection,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+    {
+        let path = Self::path(&request, None);
+        let start = std::time::Instant::now();
+        let result = self.inner.execute_many(client, request, ids).await;
+        self.report(&path, start);
+        result
+    }
+}
+
+#[async_trait]
+impl<C, E> RequestExecutor<C> for RecordingExecutor<E>
+where
+    C: ApiClient,
+    E: RequestExecutor<C> + Sync,
+{
+    type Error = RecordingError<E::Error>;
+
+    async fn execute<A>(
+        &self,
+        client: &C,
+        request: ApiRequest<A>,
+        id: Option<String>,
+    ) -> Result<A::Response, Self::Error>
+    where
+        A: ApiSelection,
+    {
+        let recorded = ApiRequest::<A> {
+            selections: request.selections.clone(),
+            query_items: request.query_items.clone(),
+            ..Default::default()
+        };
+        let response = self
+            .inner
+            .execute(client, request, id.clone())
+            .await
+            .map_err(RecordingError::Executor)?
+            .into_inner();
+        self.record(&recorded, id.as_deref(), &response.value)?;
+
+        Ok(response.into())
+    }
+
+    async fn execute_many<A, I>(
+        &self,
+        client: &C,
+        request: ApiRequest<A>,
+        ids: Vec<I>,
+    ) -> HashMap<I, Result<A::Response, Self::Error>>
+    where
+        A: ApiSelection,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+    {
+        let recorded = ApiRequest::<A> {
+            selections: request.selections.clone(),
+            query_items: request.query_items.clone(),
+            ..Default::default()
+        };
+        self.inner
+            .execute_many(client, request, ids)
+            .await
+            .into_iter()
+            .map(|(i, result)| {
+                let result = result
+                    .map_err(RecordingError::Executor)
+                    .and_then(|response| {
+                        let response = response.into_inner();
+                        self.record(&recorded, Some(&i.to_string()), &response.value)?;
+                        Ok(response.into())
+                    });
+                (i, result)
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl<C> RequestExecutor<C> for ReplayExecutor
+where
+    C: ApiClient,
+{
+    type Error = ApiClientError<std::io::Error>;
+
+    async fn execute<A>(
+        &self,
+        _client: &C,
+        request: ApiRequest<A>,
+        id: Option<String>,
+    ) -> Result<A::Response, Self::Error>
+    where
+        A: ApiSelection,
+    {
+        self.replay(&request, id.as_deref())
+    }
+
+    async fn execute_many<A, I>(
+        &self,
+        _client: &C,
+        request: ApiRequest<A>,
+        ids: Vec<I>,
+    ) -> HashMap<I, Result<A::Response, Self::Error>>
+    where
+        A: ApiSelection,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+    {
+        ids.into_iter()
+            .map(|i| {
+                let result = self.replay(&request, Some(&i.to_string()));
+                (i, result)
+            })
+            .collect()
+    }
+}
+
+/// The transport used to talk to the API. Implementations for `reqwest` and `awc` are provided
+/// behind their respective features, other transports can be plugged in by implementing this
+/// trait.
+///
+/// ```
+/// use async_trait::async_trait;
+/// use torn_api::send::ApiClient;
+///
+/// struct StaticClient;
+///
+/// #[async_trait]
+/// impl ApiClient for StaticClient {
+///     type Error = std::convert::Infallible;
+///
+///     async fn request(&self, url: String) -> Result<serde_json::Value, Self::Error> {
+///         // a real transport would fetch `url` here
+///         Ok(serde_json::json!({ "player_id": 1, "level": 15 }))
+///     }
+/// }
+///
+/// # futures::executor::block_on(async {
+/// let response = StaticClient.torn_api("key").user(|b| b).await.unwrap();
+/// assert_eq!(response.0.value["player_id"], 1);
+/// # });
+/// ```
 #[async_trait]
 pub trait ApiClient: Send + Sync {
     type Error: std::error::Error + Sync + Send;
 
     async fn request(&self, url: String) -> Result<serde_json::Value, Self::Error>;
 
-    fn torn_api<S>(&self, key: S) -> ApiProvider<Self, DirectExecutor<Self>>
+    fn torn_api<S>(&self, key: S) -> ApiProvider<'_, Self, DirectExecutor<Self>>
     where
         Self: Sized,
         S: ToString,
     {
         ApiProvider::new(self, DirectExecutor::new(key.to_string()))
     }
+
+    /// Like [`Self::torn_api`], but spreads the requests over several keys. See
+    /// [`RotatingExecutor`].
+    fn torn_api_rotating<I, S>(&self, keys: I) -> ApiProvider<'_, Self, RotatingExecutor<Self>>
+    where
+        Self: Sized,
+        I: IntoIterator<Item = S>,
+        S: ToString,
+    {
+        ApiProvider::new(self, RotatingExecutor::new(keys))
+    }
 }
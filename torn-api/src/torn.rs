@@ -3,12 +3,15 @@ use std::collections::{BTreeMap, HashMap};
 use chrono::{DateTime, Utc};
 use serde::{
     de::{self, MapAccess, Visitor},
-    Deserialize, Deserializer,
+    Deserialize,
 };
 
 use torn_api_macros::ApiCategory;
 
-use crate::{de_util, user};
+use crate::{
+    de_util::{self, empty_map, unix_timestamp},
+    user,
+};
 
 #[derive(Debug, Clone, Copy, ApiCategory)]
 #[api(category = "torn")]
@@ -23,17 +26,21 @@ pub enum TornSelection {
 
     #[api(
         type = "HashMap<String, TerritoryWar>",
-        with = "decode_territory_wars",
+        with = "empty_map",
         field = "territorywars"
     )]
     TerritoryWars,
 
-    #[api(type = "HashMap<String, Racket>", field = "rackets")]
+    #[api(
+        type = "HashMap<String, Racket>",
+        field = "rackets",
+        with = "empty_map"
+    )]
     Rackets,
 
     #[api(
         type = "HashMap<String, Territory>",
-        with = "decode_territory",
+        with = "empty_map",
         field = "territory"
     )]
     Territory,
@@ -41,20 +48,51 @@ pub enum TornSelection {
     #[api(type = "TerritoryWarReport", field = "territorywarreport")]
     TerritoryWarReport,
 
-    #[api(type = "BTreeMap<i32, Item>", field = "items")]
+    #[api(type = "BTreeMap<i32, Item>", field = "items", with = "empty_map")]
     Items,
+
+    #[api(
+        type = "chrono::DateTime<chrono::Utc>",
+        field = "timestamp",
+        with = "unix_timestamp"
+    )]
+    Timestamp,
 }
 
 pub type Selection = TornSelection;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct EliminationLeaderboard {
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub position: i16,
     pub team: user::EliminationTeam,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub score: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub lives: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(default, deserialize_with = "de_util::flexible_number_option")
+    )]
     pub participants: Option<i16>,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(default, deserialize_with = "de_util::flexible_number_option")
+    )]
     pub wins: Option<i32>,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(default, deserialize_with = "de_util::flexible_number_option")
+    )]
     pub losses: Option<i32>,
 }
 
@@ -64,15 +102,6 @@ pub enum Competition {
     Unkown(String),
 }
 
-fn decode_territory_wars<'de, D>(deserializer: D) -> Result<HashMap<String, TerritoryWar>, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    let map: Option<_> = serde::Deserialize::deserialize(deserializer)?;
-
-    Ok(map.unwrap_or_default())
-}
-
 fn decode_competition<'de, D>(deserializer: D) -> Result<Option<Competition>, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -136,8 +165,20 @@ where
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct TerritoryWar {
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub territory_war_id: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub assaulting_faction: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub defending_faction: i32,
 
     #[serde(with = "chrono::serde::ts_seconds")]
@@ -149,6 +190,10 @@ pub struct TerritoryWar {
 #[derive(Debug, Clone, Deserialize)]
 pub struct Racket {
     pub name: String,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub level: i16,
     pub reward: String,
 
@@ -158,15 +203,39 @@ pub struct Racket {
     pub changed: DateTime<Utc>,
 
     #[serde(rename = "faction")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(default, deserialize_with = "de_util::flexible_number_option")
+    )]
     pub faction_id: Option<i32>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Territory {
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub sector: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub size: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub slots: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub daily_respect: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub faction: i32,
 
     pub neighbors: Vec<String>,
@@ -174,13 +243,6 @@ pub struct Territory {
     pub racket: Option<Racket>,
 }
 
-fn decode_territory<'de, D>(deserializer: D) -> Result<HashMap<String, Territory>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    Ok(Option::deserialize(deserializer)?.unwrap_or_default())
-}
-
 #[derive(Clone, Debug, Deserialize)]
 pub struct TerritoryWarReportTerritory {
     pub name: String,
@@ -215,8 +277,20 @@ pub enum TerritoryWarReportRole {
 #[derive(Debug, Clone, Deserialize)]
 pub struct TerritoryWarReportFaction {
     pub name: String,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub score: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub joins: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub clears: i32,
     #[serde(rename = "type")]
     pub role: TerritoryWarReportRole,
@@ -226,6 +300,7 @@ pub struct TerritoryWarReportFaction {
 pub struct TerritoryWarReport {
     pub territory: TerritoryWarReportTerritory,
     pub war: TerritoryWarReportWar,
+    #[serde(deserialize_with = "de_util::empty_map")]
     pub factions: HashMap<i32, TerritoryWarReportFaction>,
 }
 
@@ -304,6 +379,67 @@ mod tests {
     use super::*;
     use crate::tests::{async_test, setup, Client, ClientTrait};
 
+    #[cfg(feature = "lenient")]
+    #[test]
+    fn lenient_numbers() {
+        let racket = |faction: serde_json::Value| {
+            let mut racket = serde_json::json!({
+                "name": "Gaming Den",
+                "level": "3",
+                "reward": "Daily gaming tokens",
+                "created": 1700000000,
+                "changed": 1700000000
+            });
+            if !faction.is_null() {
+                racket["faction"] = faction;
+            }
+            serde_json::from_value::<Racket>(racket).unwrap()
+        };
+
+        let with_faction = racket(serde_json::json!("9533"));
+        assert_eq!(with_faction.level, 3);
+        assert_eq!(with_faction.faction_id, Some(9533));
+        assert_eq!(racket(serde_json::Value::Null).faction_id, None);
+    }
+
+    #[test]
+    fn competition_variants() {
+        let decode = |competition| {
+            Response::from(
+                crate::ApiResponse::from_value(serde_json::json!({ "competition": competition }))
+                    .unwrap(),
+            )
+            .competition()
+            .unwrap()
+        };
+
+        let elimination = decode(serde_json::json!({
+            "name": "Elimination",
+            "teams": [{
+                "position": 1,
+                "team": "cheese",
+                "score": 120,
+                "lives": 8,
+                "participants": 600,
+                "wins": 40,
+                "losses": 12
+            }]
+        }));
+        match elimination {
+            Some(Competition::Elimination { teams }) => {
+                assert_eq!(teams.len(), 1);
+                assert!(matches!(teams[0].team, user::EliminationTeam::Cheese));
+            }
+            other => panic!("unexpected competition: {other:?}"),
+        }
+
+        let unknown = decode(serde_json::json!({ "name": "Easter Egg Hunt" }));
+        assert!(matches!(unknown, Some(Competition::Unkown(name)) if name == "Easter Egg Hunt"));
+
+        assert!(decode(serde_json::json!({ "name": "" })).is_none());
+        assert!(decode(serde_json::Value::Null).is_none());
+    }
+
     #[async_test]
     async fn competition() {
         let key = setup();
@@ -414,4 +550,26 @@ mod tests {
         let item_list = response.items().unwrap();
         assert!(item_list.contains_key(&837));
     }
+
+    #[async_test]
+    async fn server_time() {
+        let key = setup();
+
+        let time = Client::default().torn_api(key).server_time().await.unwrap();
+
+        assert!(time.0.timestamp() > 1_700_000_000);
+    }
+
+    #[async_test]
+    async fn timestamp() {
+        let key = setup();
+
+        let response = Client::default()
+            .torn_api(key)
+            .torn(|b| b.selections([Selection::Timestamp]))
+            .await
+            .unwrap();
+
+        response.timestamp().unwrap();
+    }
 }
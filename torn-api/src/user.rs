@@ -1,6 +1,6 @@
 use serde::{
     de::{self, MapAccess, Visitor},
-    Deserialize, Deserializer,
+    Deserialize, Deserializer, Serialize,
 };
 use std::{
     collections::{BTreeMap, HashMap},
@@ -9,7 +9,7 @@ use std::{
 
 use torn_api_macros::{ApiCategory, IntoOwned};
 
-use crate::de_util;
+use crate::de_util::{self, empty_map, unix_timestamp};
 
 pub use crate::common::{Attack, AttackFull, LastAction, Status};
 
@@ -27,16 +27,35 @@ pub enum UserSelection {
     PersonalStats,
     #[api(type = "CriminalRecord", field = "criminalrecord")]
     Crimes,
-    #[api(type = "BTreeMap<i32, Attack>", field = "attacks")]
+    #[api(type = "BTreeMap<i32, Attack>", field = "attacks", with = "empty_map")]
     AttacksFull,
-    #[api(type = "BTreeMap<i32, AttackFull>", field = "attacks")]
+    #[api(
+        type = "BTreeMap<i32, AttackFull>",
+        field = "attacks",
+        with = "empty_map"
+    )]
     Attacks,
-    #[api(type = "HashMap<Icon, &str>", field = "icons")]
+    #[api(type = "HashMap<Icon, &str>", field = "icons", with = "empty_map")]
     Icons,
+    #[api(type = "BTreeMap<String, Event>", field = "events", with = "empty_map")]
+    Events,
+    /// The events which the key owner hasn't seen yet.
+    #[api(type = "BTreeMap<String, Event>", field = "events", with = "empty_map")]
+    NewEvents,
     #[api(type = "Awards<Medals>", flatten)]
     Medals,
     #[api(type = "Awards<Honors>", flatten)]
     Honors,
+    /// The names of all selections of the category. The API doesn't offer a way to look up
+    /// users by name.
+    #[api(type = "Vec<&str>", field = "selections")]
+    Lookup,
+    #[api(
+        type = "chrono::DateTime<chrono::Utc>",
+        field = "timestamp",
+        with = "unix_timestamp"
+    )]
+    Timestamp,
 }
 
 pub type Selection = UserSelection;
@@ -149,8 +168,16 @@ where
 
 #[derive(Debug, IntoOwned, Deserialize)]
 pub struct Basic<'a> {
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub player_id: i32,
     pub name: &'a str,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub level: i16,
     pub gender: Gender,
     pub status: Status<'a>,
@@ -170,8 +197,20 @@ pub struct Discord {
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct LifeBar {
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub current: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub maximum: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub increment: i16,
 }
 
@@ -348,11 +387,23 @@ where
 
 #[derive(Debug, IntoOwned, Deserialize)]
 pub struct Profile<'a> {
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub player_id: i32,
     pub name: &'a str,
     pub rank: &'a str,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub level: i16,
     pub gender: Gender,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub age: i32,
 
     pub life: LifeBar,
@@ -365,69 +416,297 @@ pub struct Profile<'a> {
     #[serde(deserialize_with = "deserialize_comp")]
     pub competition: Option<Competition>,
 
-    #[serde(deserialize_with = "de_util::int_is_bool")]
+    #[cfg_attr(
+        not(feature = "lenient"),
+        serde(deserialize_with = "de_util::int_is_bool")
+    )]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_bool")
+    )]
     pub revivable: bool,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Event {
+    #[serde(deserialize_with = "unix_timestamp")]
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// The text of the event, which may contain html.
+    pub event: String,
+    #[cfg_attr(
+        not(feature = "lenient"),
+        serde(deserialize_with = "de_util::int_is_bool")
+    )]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_bool")
+    )]
+    pub seen: bool,
+}
+
+/// Remembers which events have already been returned, so that polling the `events` selection
+/// only yields new ones. The cursor can be serialised to resume polling after a restart.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventCursor {
+    /// The timestamp of the newest event seen so far.
+    pub timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    /// The ids of the events with exactly that timestamp, since several events can happen in
+    /// the same second.
+    pub ids: Vec<String>,
+}
+
+impl EventCursor {
+    /// Returns the events which are newer than the cursor, oldest first, and moves the cursor
+    /// past them.
+    pub fn advance(&mut self, events: BTreeMap<String, Event>) -> Vec<(String, Event)> {
+        let mut new: Vec<_> = events
+            .into_iter()
+            .filter(|(id, event)| match self.timestamp {
+                Some(timestamp) => {
+                    event.timestamp > timestamp
+                        || (event.timestamp == timestamp && !self.ids.contains(id))
+                }
+                None => true,
+            })
+            .collect();
+        new.sort_by(|(a_id, a), (b_id, b)| a.timestamp.cmp(&b.timestamp).then(a_id.cmp(b_id)));
+
+        if let Some((_, newest)) = new.last() {
+            let newest = newest.timestamp;
+            if self.timestamp != Some(newest) {
+                self.timestamp = Some(newest);
+                self.ids.clear();
+            }
+            self.ids.extend(
+                new.iter()
+                    .filter(|(_, event)| event.timestamp == newest)
+                    .map(|(id, _)| id.clone()),
+            );
+        }
+
+        new
+    }
+}
+
+impl crate::ApiRequestBuilder<UserSelection> {
+    /// Restricts the `personalstats` selection to the given stats. Responses to such requests
+    /// only contain the requested stats and should be read with [`Response::personal_stats_map`].
+    #[must_use]
+    pub fn stats<I, S>(mut self, stats: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let stats: Vec<_> = stats.into_iter().map(|s| s.as_ref().to_owned()).collect();
+        self.request
+            .add_query_item("stat", crate::join_list(stats.iter().map(String::as_str)));
+        self
+    }
+}
+
+impl Response {
+    /// The personal stats of a request made with [`crate::ApiRequestBuilder::stats`], keyed by
+    /// their raw names.
+    pub fn personal_stats_map(&self) -> serde_json::Result<BTreeMap<String, i64>> {
+        self.0.decode_field("personalstats", "personalstats")
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct PersonalStats {
     #[serde(rename = "attackswon")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub attacks_won: i32,
     #[serde(rename = "attackslost")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub attacks_lost: i32,
     #[serde(rename = "defendswon")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub defends_won: i32,
     #[serde(rename = "defendslost")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub defends_lost: i32,
     #[serde(rename = "statenhancersused")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub stat_enhancers_used: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub refills: i32,
     #[serde(rename = "drugsused")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub drugs_used: i32,
     #[serde(rename = "xantaken")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub xanax_taken: i32,
     #[serde(rename = "lsdtaken")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub lsd_taken: i32,
     #[serde(rename = "networth")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub net_worth: i64,
     #[serde(rename = "energydrinkused")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub cans_used: i32,
     #[serde(rename = "boostersused")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub boosters_used: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub awards: i16,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub elo: i16,
     #[serde(rename = "daysbeendonator")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub days_been_donator: i16,
     #[serde(rename = "bestdamage")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub best_damage: i32,
 }
 
 #[derive(Deserialize)]
 pub struct Crimes1 {
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub selling_illegal_products: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub theft: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub auto_theft: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub drug_deals: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub computer_crimes: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub murder: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub fraud_crimes: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub other: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub total: i32,
 }
 
 #[derive(Deserialize)]
 pub struct Crimes2 {
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub vandalism: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub theft: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub counterfeiting: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub fraud: i32,
     #[serde(rename = "illicitservices")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub illicit_services: i32,
     #[serde(rename = "cybercrime")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub cyber_crime: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub extortion: i32,
     #[serde(rename = "illegalproduction")]
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub illegal_production: i32,
+    #[cfg_attr(
+        feature = "lenient",
+        serde(deserialize_with = "de_util::flexible_number")
+    )]
     pub total: i32,
 }
 
@@ -736,6 +1015,108 @@ mod tests {
     use super::*;
     use crate::tests::{async_test, setup, Client, ClientTrait};
 
+    #[test]
+    fn discord_renames() {
+        let linked: Discord = serde_json::from_value(serde_json::json!({
+            "userID": 2111649,
+            "discordID": "250634592474628096"
+        }))
+        .unwrap();
+        assert_eq!(
+            linked,
+            Discord {
+                user_id: Some(2111649),
+                discord_id: Some(250634592474628096)
+            }
+        );
+
+        let unlinked: Discord =
+            serde_json::from_value(serde_json::json!({ "userID": "", "discordID": "" })).unwrap();
+        assert_eq!(
+            unlinked,
+            Discord {
+                user_id: None,
+                discord_id: None
+            }
+        );
+    }
+
+    #[test]
+    fn personal_stats_request() {
+        let url = crate::ApiRequestBuilder::<Selection>::default()
+            .selections([Selection::PersonalStats])
+            .stats(["xantaken", "refills"])
+            .request
+            .url("", None);
+
+        assert_eq!(
+            url,
+            "https://api.torn.com/user/?selections=personalstats&key=&stat=xantaken,refills"
+        );
+
+        let response = Response::from(
+            crate::ApiResponse::from_value(serde_json::json!({
+                "personalstats": { "xantaken": 12, "refills": 3 }
+            }))
+            .unwrap(),
+        );
+        let stats = response.personal_stats_map().unwrap();
+        assert_eq!(stats.get("xantaken"), Some(&12));
+        assert!(response.personal_stats().is_err());
+    }
+
+    #[test]
+    fn event_cursor() {
+        let response = |events| {
+            Response::from(
+                crate::ApiResponse::from_value(serde_json::json!({ "events": events })).unwrap(),
+            )
+            .events()
+            .unwrap()
+        };
+
+        let mut cursor = EventCursor::default();
+        let events = cursor.advance(response(serde_json::json!({
+            "b": { "timestamp": 20, "event": "second", "seen": 0 },
+            "a": { "timestamp": 10, "event": "first", "seen": 1 },
+        })));
+        assert_eq!(
+            events.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert!(events[0].1.seen);
+
+        let events = cursor.advance(response(serde_json::json!({
+            "b": { "timestamp": 20, "event": "second", "seen": 1 },
+            "c": { "timestamp": 20, "event": "third", "seen": 0 },
+            "d": { "timestamp": 30, "event": "fourth", "seen": 0 },
+        })));
+        assert_eq!(
+            events.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(),
+            ["c", "d"]
+        );
+
+        assert!(cursor.advance(response(serde_json::json!([]))).is_empty());
+
+        let restored: EventCursor =
+            serde_json::from_value(serde_json::to_value(&cursor).unwrap()).unwrap();
+        assert_eq!(restored, cursor);
+        assert_eq!(restored.ids, ["d"]);
+    }
+
+    #[async_test]
+    async fn profile_shortcut() {
+        let key = setup();
+
+        let profile = Client::default()
+            .torn_api(key)
+            .user_profile(Some(1))
+            .await
+            .unwrap();
+
+        assert_eq!(profile.player_id, 1);
+    }
+
     #[async_test]
     async fn user() {
         let key = setup();
@@ -0,0 +1,93 @@
+//! Compares how fast concurrent workers acquire keys with the two isolation modes. The keys are
+//! stored in the schema `key_pool_bench` of the database at `DATABASE_URL`.
+
+use std::{sync::Arc, time::Duration};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use sqlx::{postgres::PgPoolOptions, Executor};
+use torn_key_pool::{
+    postgres::{Isolation, PgKeyPoolStorage},
+    KeyDomain, KeyPoolStorage,
+};
+
+const KEYS: usize = 10;
+const WORKERS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Domain {
+    All,
+}
+
+impl KeyDomain for Domain {}
+
+pub fn isolation_benchmark(c: &mut Criterion) {
+    dotenvy::dotenv().ok();
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let pool = rt.block_on(async {
+        let url = std::env::var("DATABASE_URL").expect("database url");
+        let pool = PgPoolOptions::new()
+            .after_connect(|conn, _| {
+                Box::pin(async move {
+                    conn.execute("set search_path to key_pool_bench").await?;
+                    Ok(())
+                })
+            })
+            .connect(&url)
+            .await
+            .unwrap();
+        pool.execute("drop schema if exists key_pool_bench cascade; create schema key_pool_bench")
+            .await
+            .unwrap();
+
+        let storage = PgKeyPoolStorage::<Domain>::new(pool.clone(), i16::MAX);
+        storage.initialise().await.unwrap();
+        for i in 0..KEYS {
+            storage
+                .store_key(1, format!("{i:0>16}"), vec![Domain::All])
+                .await
+                .unwrap();
+        }
+
+        pool
+    });
+
+    let mut group = c.benchmark_group("acquire under concurrency");
+    for isolation in [Isolation::RepeatableRead, Isolation::SkipLocked] {
+        let storage = Arc::new(
+            PgKeyPoolStorage::<Domain>::new(pool.clone(), i16::MAX).with_isolation(isolation),
+        );
+
+        group.bench_function(format!("{isolation:?}"), |b| {
+            b.to_async(&rt).iter_custom(|iters| {
+                let storage = storage.clone();
+                async move {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        storage.reset_all_usage().await.unwrap();
+
+                        let start = std::time::Instant::now();
+                        let mut set = tokio::task::JoinSet::new();
+                        for _ in 0..WORKERS {
+                            let storage = storage.clone();
+                            set.spawn(async move { storage.acquire_key(Domain::All).await });
+                        }
+                        while let Some(result) = set.join_next().await {
+                            result.unwrap().unwrap();
+                        }
+                        elapsed += start.elapsed();
+                    }
+                    elapsed
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, isolation_benchmark);
+criterion_main!(benches);
@@ -4,6 +4,8 @@
 pub mod postgres;
 
 // pub mod local;
+#[cfg(feature = "tokio-runtime")]
+pub mod rate_limit;
 pub mod send;
 
 use std::sync::Arc;
@@ -13,25 +15,124 @@ use thiserror::Error;
 
 use torn_api::ResponseError;
 
+/// Cooperative cancellation of bulk requests. Cloned tokens share their state.
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    sender: Arc<std::sync::Mutex<Option<futures::channel::oneshot::Sender<()>>>>,
+    receiver: futures::future::Shared<futures::channel::oneshot::Receiver<()>>,
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        use futures::FutureExt;
+
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        Self {
+            sender: Arc::new(std::sync::Mutex::new(Some(sender))),
+            receiver: receiver.shared(),
+        }
+    }
+
+    pub fn cancel(&self) {
+        if let Some(sender) = self.sender.lock().unwrap().take() {
+            _ = sender.send(());
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.sender.lock().unwrap().is_none()
+    }
+
+    /// Resolves once [`Self::cancel`] has been called on this token or one of its clones.
+    pub async fn cancelled(&self) {
+        if self.receiver.clone().await.is_err() {
+            // all senders were dropped without cancelling
+            futures::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Reference counted error, used to hand the same storage error to every request of a bulk
+/// execution without requiring the error type itself to be `Clone`.
+#[derive(Debug)]
+pub struct SharedError<E>(Arc<E>);
+
+impl<E> Clone for SharedError<E> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<E> From<E> for SharedError<E> {
+    fn from(value: E) -> Self {
+        Self(Arc::new(value))
+    }
+}
+
+impl<E> std::ops::Deref for SharedError<E> {
+    type Target = E;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<E> std::fmt::Display for SharedError<E>
+where
+    E: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<E> std::error::Error for SharedError<E>
+where
+    E: std::error::Error,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum KeyPoolError<S, C>
 where
-    S: std::error::Error + Clone,
+    S: std::error::Error,
     C: std::error::Error,
 {
     #[error("Key pool storage driver error: {0:?}")]
-    Storage(#[source] S),
+    Storage(#[source] SharedError<S>),
 
     #[error(transparent)]
     Client(#[from] C),
 
     #[error(transparent)]
     Response(ResponseError),
+
+    #[error("Request was cancelled")]
+    Cancelled,
+}
+
+impl<S, C> torn_api::FromResponseError for KeyPoolError<S, C>
+where
+    S: std::error::Error,
+    C: std::error::Error,
+{
+    fn from_response_error(error: ResponseError) -> Self {
+        Self::Response(error)
+    }
 }
 
 impl<S, C> KeyPoolError<S, C>
 where
-    S: std::error::Error + Clone,
+    S: std::error::Error,
     C: std::error::Error,
 {
     #[inline(always)]
@@ -50,6 +151,12 @@ pub trait ApiKey: Sync + Send + std::fmt::Debug + Clone + 'static {
 
     fn id(&self) -> Self::IdType;
 
+    /// The access level of the key (see [`torn_api::key::access_level`]), if the storage
+    /// tracks it.
+    fn access_level(&self) -> Option<i16> {
+        None
+    }
+
     fn selector<D>(&self) -> KeySelector<Self, D>
     where
         D: KeyDomain,
@@ -75,6 +182,22 @@ where
     UserId(i32),
     Has(Vec<D>),
     OneOf(Vec<D>),
+    /// Matches keys with any domain of the given kind, regardless of the domain's other fields.
+    /// This requires the domain to be serialised in an internally tagged representation, i.e.
+    /// with `#[serde(tag = "type")]`.
+    HasKind(&'static str),
+    /// Matches keys selected by the inner selector which have at least `min_remaining` uses
+    /// left in the current minute. See [`KeySelector::with_budget`].
+    WithBudget {
+        selector: Box<KeySelector<K, D>>,
+        min_remaining: i16,
+    },
+    /// Matches keys selected by the inner selector whose access level is at least `min_level`.
+    /// See [`KeySelector::with_access_level`].
+    WithAccessLevel {
+        selector: Box<KeySelector<K, D>>,
+        min_level: i16,
+    },
 }
 
 impl<K, D> KeySelector<K, D>
@@ -82,9 +205,50 @@ where
     K: ApiKey,
     D: KeyDomain,
 {
+    /// Only matches keys with at least `min_remaining` uses left in the current minute, to
+    /// avoid picking a nearly exhausted key for a job which needs several requests.
+    pub fn with_budget(self, min_remaining: i16) -> Self {
+        Self::WithBudget {
+            selector: Box::new(self),
+            min_remaining,
+        }
+    }
+
+    /// Only matches keys with an access level of at least `min_level`. Keys whose access level
+    /// is unknown never match.
+    pub fn with_access_level(self, min_level: i16) -> Self {
+        Self::WithAccessLevel {
+            selector: Box::new(self),
+            min_level,
+        }
+    }
+
+    /// The domain a key is requested for, if the selector names exactly one.
+    pub(crate) fn single_domain(&self) -> Option<&D> {
+        match self {
+            Self::Has(domains) | Self::OneOf(domains) if domains.len() == 1 => domains.first(),
+            Self::WithBudget { selector, .. } | Self::WithAccessLevel { selector, .. } => {
+                selector.single_domain()
+            }
+            _ => None,
+        }
+    }
+
     pub(crate) fn fallback(&self) -> Option<Self> {
         match self {
-            Self::Key(_) | Self::UserId(_) | Self::Id(_) => None,
+            Self::Key(_) | Self::UserId(_) | Self::Id(_) | Self::HasKind(_) => None,
+            Self::WithBudget {
+                selector,
+                min_remaining,
+            } => selector
+                .fallback()
+                .map(|fallback| fallback.with_budget(*min_remaining)),
+            Self::WithAccessLevel {
+                selector,
+                min_level,
+            } => selector
+                .fallback()
+                .map(|fallback| fallback.with_access_level(*min_level)),
             Self::Has(domains) => {
                 let fallbacks: Vec<_> = domains.iter().filter_map(|d| d.fallback()).collect();
                 if fallbacks.is_empty() {
@@ -146,12 +310,30 @@ where
 pub trait KeyPoolStorage {
     type Key: ApiKey;
     type Domain: KeyDomain;
-    type Error: std::error::Error + Sync + Send + Clone;
+    type Error: std::error::Error + Sync + Send;
 
     async fn acquire_key<S>(&self, selector: S) -> Result<Self::Key, Self::Error>
     where
         S: IntoSelector<Self::Key, Self::Domain>;
 
+    /// Same as [`Self::acquire_key`], but also returns the selector which matched the key. It
+    /// differs from the requested selector if the key was only found through the
+    /// [`KeyDomain::fallback`] of the requested domains. The default implementation can't tell
+    /// and always returns the requested selector.
+    async fn acquire_key_matched<S>(
+        &self,
+        selector: S,
+    ) -> Result<(Self::Key, KeySelector<Self::Key, Self::Domain>), Self::Error>
+    where
+        Self: Sync,
+        S: IntoSelector<Self::Key, Self::Domain>,
+    {
+        let selector = selector.into_selector();
+        let key = self.acquire_key(selector.clone()).await?;
+
+        Ok((key, selector))
+    }
+
     async fn acquire_many_keys<S>(
         &self,
         selector: S,
@@ -160,6 +342,43 @@ pub trait KeyPoolStorage {
     where
         S: IntoSelector<Self::Key, Self::Domain>;
 
+    /// Same as [`Self::acquire_many_keys`], but returns every key only once together with the
+    /// number of uses which were allocated to it.
+    async fn acquire_many_keys_detailed<S>(
+        &self,
+        selector: S,
+        number: i64,
+    ) -> Result<Vec<(Self::Key, u16)>, Self::Error>
+    where
+        Self: Sync,
+        S: IntoSelector<Self::Key, Self::Domain>,
+    {
+        let keys = self.acquire_many_keys(selector, number).await?;
+
+        let mut detailed: Vec<(Self::Key, u16)> = Vec::new();
+        for key in keys {
+            match detailed.iter_mut().find(|(k, _)| k.id() == key.id()) {
+                Some((_, count)) => *count += 1,
+                None => detailed.push((key, 1)),
+            }
+        }
+
+        Ok(detailed)
+    }
+
+    /// Acquires the key with the given id regardless of its cooldown and uses. The default
+    /// implementation can't bypass them and acquires the key through [`Self::acquire_key`].
+    async fn force_acquire_key(
+        &self,
+        id: <Self::Key as ApiKey>::IdType,
+    ) -> Result<Self::Key, Self::Error>
+    where
+        Self: Sync,
+    {
+        self.acquire_key(KeySelector::<Self::Key, Self::Domain>::Id(id))
+            .await
+    }
+
     async fn flag_key(&self, key: Self::Key, code: u8) -> Result<bool, Self::Error>;
 
     async fn store_key(
@@ -169,6 +388,22 @@ pub trait KeyPoolStorage {
         domains: Vec<Self::Domain>,
     ) -> Result<Self::Key, Self::Error>;
 
+    /// Stores several keys at once. The default implementation stores them one after the other
+    /// through [`Self::store_key`].
+    async fn store_keys<I>(&self, entries: I) -> Result<Vec<Self::Key>, Self::Error>
+    where
+        Self: Sync,
+        I: IntoIterator<Item = (i32, String, Vec<Self::Domain>)> + Send,
+        I::IntoIter: Send,
+    {
+        let mut keys = Vec::new();
+        for (user_id, key, domains) in entries {
+            keys.push(self.store_key(user_id, key, domains).await?);
+        }
+
+        Ok(keys)
+    }
+
     async fn read_key<S>(&self, selector: S) -> Result<Option<Self::Key>, Self::Error>
     where
         S: IntoSelector<Self::Key, Self::Domain>;
@@ -181,6 +416,21 @@ pub trait KeyPoolStorage {
     where
         S: IntoSelector<Self::Key, Self::Domain>;
 
+    /// Removes every key matching the selector. The default implementation reads the matching
+    /// keys and removes them one after the other through [`Self::remove_key`].
+    async fn remove_keys<S>(&self, selector: S) -> Result<Vec<Self::Key>, Self::Error>
+    where
+        Self: Sync,
+        S: IntoSelector<Self::Key, Self::Domain>,
+    {
+        let mut removed = Vec::new();
+        for key in self.read_keys(selector).await? {
+            removed.push(self.remove_key(KeySelector::Id(key.id())).await?);
+        }
+
+        Ok(removed)
+    }
+
     async fn add_domain_to_key<S>(
         &self,
         selector: S,
@@ -206,14 +456,193 @@ pub trait KeyPoolStorage {
         S: IntoSelector<Self::Key, Self::Domain>;
 }
 
+/// Storages which can reserve several uses of a single key at once, see [`KeyReservation`].
+#[async_trait]
+pub trait KeyReservationStorage: KeyPoolStorage {
+    /// Acquires a key with at least `uses` uses left in the current minute and reserves all of
+    /// them at once.
+    async fn reserve_key<S>(&self, selector: S, uses: i16) -> Result<Self::Key, Self::Error>
+    where
+        S: IntoSelector<Self::Key, Self::Domain>;
+
+    /// Returns uses which were reserved through [`Self::reserve_key`] but not needed.
+    async fn release_uses(&self, key: Self::Key, uses: i16) -> Result<(), Self::Error>;
+}
+
+/// A key with several of its uses reserved for the current minute, obtained through
+/// [`KeyReservationStorage::reserve_key`].
+///
+/// Uses which weren't taken are returned to the storage by [`Self::release`], or in the
+/// background when the reservation is dropped inside of a tokio or actix runtime. The latter
+/// includes tasks which panicked or returned early while holding the reservation.
+#[derive(Debug)]
+pub struct KeyReservation<S>
+where
+    S: KeyReservationStorage + Clone + Send + Sync + 'static,
+{
+    storage: S,
+    key: Option<S::Key>,
+    remaining: std::sync::atomic::AtomicI16,
+    release_on_drop: bool,
+}
+
+impl<S> KeyReservation<S>
+where
+    S: KeyReservationStorage + Clone + Send + Sync + 'static,
+{
+    pub fn new(storage: S, key: S::Key, uses: i16) -> Self {
+        Self {
+            storage,
+            key: Some(key),
+            remaining: std::sync::atomic::AtomicI16::new(uses),
+            release_on_drop: true,
+        }
+    }
+
+    /// Whether the remaining uses are returned to the storage when the reservation is dropped
+    /// without calling [`Self::release`]. Enabled by default.
+    pub fn release_on_drop(mut self, release: bool) -> Self {
+        self.release_on_drop = release;
+        self
+    }
+
+    pub fn key(&self) -> &S::Key {
+        self.key.as_ref().unwrap()
+    }
+
+    /// The number of reserved uses which haven't been taken yet.
+    pub fn remaining(&self) -> i16 {
+        self.remaining.load(std::sync::atomic::Ordering::Acquire)
+    }
+
+    /// Takes one of the reserved uses, returning `None` once all of them have been used up.
+    pub fn take_use(&self) -> Option<&S::Key> {
+        use std::sync::atomic::Ordering;
+
+        self.remaining
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |r| {
+                (r > 0).then_some(r - 1)
+            })
+            .ok()
+            .map(|_| self.key())
+    }
+
+    /// Ends the reservation and returns the remaining uses to the storage.
+    pub async fn release(mut self) -> Result<(), S::Error> {
+        let remaining = self.remaining();
+        let key = self.key.take().unwrap();
+        if remaining > 0 {
+            self.storage.release_uses(key, remaining).await
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<S> Drop for KeyReservation<S>
+where
+    S: KeyReservationStorage + Clone + Send + Sync + 'static,
+{
+    fn drop(&mut self) {
+        let remaining = self.remaining();
+        let Some(key) = self.key.take() else {
+            return;
+        };
+        if remaining <= 0 || !self.release_on_drop {
+            return;
+        }
+
+        let storage = self.storage.clone();
+        let release = async move {
+            _ = storage.release_uses(key, remaining).await;
+        };
+
+        #[cfg(feature = "tokio-runtime")]
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(release);
+        }
+
+        #[cfg(all(not(feature = "tokio-runtime"), feature = "actix-runtime"))]
+        if actix_rt::System::try_current().is_some() {
+            actix_rt::spawn(release);
+        }
+
+        // without a runtime the uses simply expire at the end of the minute
+        #[cfg(not(any(feature = "tokio-runtime", feature = "actix-runtime")))]
+        drop(release);
+    }
+}
+
+/// Snapshot of the progress of a bulk request, see [`send::PoolBuilder::on_bulk_backlog`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BulkBacklog {
+    /// Requests which were started and haven't finished yet.
+    pub in_flight: usize,
+    /// In-flight requests which are still waiting for a key because the pool couldn't provide
+    /// one when they were started.
+    pub waiting_for_key: usize,
+    /// Requests which haven't been started yet because of the concurrency limit.
+    pub queued: usize,
+}
+
+pub(crate) struct BacklogHook(Box<dyn Fn(BulkBacklog) + Send + Sync + 'static>);
+
+impl std::fmt::Debug for BacklogHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("BacklogHook")
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct PoolOptions {
     comment: Option<String>,
+    query_items: Vec<(&'static str, String)>,
+    bulk_concurrency: Option<usize>,
+    bulk_retry_budget: Option<usize>,
+    backlog_hook: Option<BacklogHook>,
     hooks_before: std::collections::HashMap<std::any::TypeId, Box<dyn std::any::Any + Send + Sync>>,
     hooks_after: std::collections::HashMap<std::any::TypeId, Box<dyn std::any::Any + Send + Sync>>,
 }
 
-#[derive(Debug, Clone)]
+impl PoolOptions {
+    fn bulk_concurrency(&self) -> usize {
+        self.bulk_concurrency.unwrap_or(25)
+    }
+}
+
+/// Keys which were acquired ahead of time, see [`send::KeyPool::warm`]. The storage only counts
+/// their uses for the minute in which they were acquired, so they expire at the end of it.
+#[derive(Debug)]
+pub(crate) struct WarmKeys<K> {
+    keys: std::sync::Mutex<Vec<K>>,
+    expires: std::time::SystemTime,
+}
+
+impl<K> WarmKeys<K> {
+    pub(crate) fn new(keys: Vec<K>) -> Self {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let next_minute = std::time::Duration::from_secs((now.as_secs() / 60 + 1) * 60);
+
+        Self {
+            keys: std::sync::Mutex::new(keys),
+            expires: std::time::UNIX_EPOCH + next_minute,
+        }
+    }
+
+    /// Takes up to `number` keys out of the cache.
+    pub(crate) fn take(&self, number: usize) -> Vec<K> {
+        let mut keys = self.keys.lock().unwrap();
+        if std::time::SystemTime::now() >= self.expires {
+            keys.clear();
+        }
+        let rest = keys.len().saturating_sub(number);
+        keys.split_off(rest)
+    }
+}
+
+#[derive(Debug)]
 pub struct KeyPoolExecutor<'a, C, S>
 where
     S: KeyPoolStorage,
@@ -221,6 +650,8 @@ where
     storage: &'a S,
     options: Arc<PoolOptions>,
     selector: KeySelector<S::Key, S::Domain>,
+    forced: bool,
+    warm: Option<Arc<WarmKeys<S::Key>>>,
     _marker: std::marker::PhantomData<C>,
 }
 
@@ -237,10 +668,59 @@ where
             storage,
             selector,
             options,
+            forced: false,
+            warm: None,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Uses exactly the key with the given id, even if it is on a cooldown or has exceeded its
+    /// rate limit. Meant for debugging individual keys.
+    pub fn forced(
+        storage: &'a S,
+        id: <S::Key as ApiKey>::IdType,
+        options: Arc<PoolOptions>,
+    ) -> Self {
+        Self {
+            storage,
+            selector: KeySelector::Id(id),
+            options,
+            forced: true,
+            warm: None,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+// manual impl so that neither the client nor the storage have to be `Clone`
+impl<C, S> Clone for KeyPoolExecutor<'_, C, S>
+where
+    S: KeyPoolStorage,
+{
+    fn clone(&self) -> Self {
+        Self {
+            storage: self.storage,
+            options: self.options.clone(),
+            selector: self.selector.clone(),
+            forced: self.forced,
+            warm: self.warm.clone(),
             _marker: std::marker::PhantomData,
         }
     }
 }
 
 #[cfg(all(test, feature = "postgres"))]
-mod test {}
+mod test {
+    use super::*;
+
+    #[test]
+    fn cancellation_token() {
+        let token = CancellationToken::new();
+        let cloned = token.clone();
+        assert!(!cloned.is_cancelled());
+
+        token.cancel();
+        assert!(cloned.is_cancelled());
+        futures::executor::block_on(cloned.cancelled());
+    }
+}
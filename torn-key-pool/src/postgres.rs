@@ -5,7 +5,7 @@ use indoc::indoc;
 use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
 use thiserror::Error;
 
-use crate::{ApiKey, IntoSelector, KeyDomain, KeyPoolStorage, KeySelector};
+use crate::{ApiKey, IntoSelector, KeyDomain, KeyPoolStorage, KeyReservationStorage, KeySelector};
 
 pub trait PgKeyDomain:
     KeyDomain + serde::Serialize + serde::de::DeserializeOwned + Eq + Unpin
@@ -25,8 +25,15 @@ where
     #[error(transparent)]
     Pg(Arc<sqlx::Error>),
 
-    #[error("No key avalaible for domain {0:?}")]
-    Unavailable(KeySelector<PgKey<D>, D>),
+    /// No usable key matches the selector, which usually means that the pool is misconfigured.
+    /// Keys which were flagged as permanently unusable don't count.
+    #[error("No key matches the selector {0:?}")]
+    NoMatchingKey(KeySelector<PgKey<D>, D>),
+
+    /// All keys matching the selector are rate limited or on a cooldown, retrying later might
+    /// succeed.
+    #[error("All keys matching the selector {0:?} are exhausted")]
+    AllKeysExhausted(KeySelector<PgKey<D>, D>),
 
     #[error("Key not found: '{0:?}'")]
     KeyNotFound(KeySelector<PgKey<D>, D>),
@@ -51,41 +58,72 @@ where
     pub key: String,
     pub uses: i16,
     pub domains: sqlx::types::Json<Vec<D>>,
+    pub access_level: Option<i16>,
+    /// The time at which the key was acquired, only set for keys returned by
+    /// [`KeyPoolStorage::acquire_key`] and [`KeyReservationStorage::reserve_key`].
+    #[sqlx(default)]
+    pub acquired_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[inline(always)]
-fn build_predicate<'b, D>(
-    builder: &mut QueryBuilder<'b, Postgres>,
-    selector: &'b KeySelector<PgKey<D>, D>,
-) where
-    D: PgKeyDomain,
-{
-    match selector {
-        KeySelector::Id(id) => builder.push("id=").push_bind(id),
-        KeySelector::UserId(user_id) => builder.push("user_id=").push_bind(user_id),
-        KeySelector::Key(key) => builder.push("key=").push_bind(key),
-        KeySelector::Has(domains) => builder
-            .push("domains @> ")
-            .push_bind(sqlx::types::Json(domains)),
-        KeySelector::OneOf(domains) => {
-            if domains.is_empty() {
-                builder.push("false");
-                return;
-            }
+/// A key together with its state, as produced by [`PgKeyPoolStorage::export_keys`]. Meant for
+/// backups and for moving a pool between databases.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, serde::Serialize, serde::Deserialize)]
+pub struct KeyRecord<D> {
+    pub user_id: i32,
+    pub key: String,
+    #[sqlx(json)]
+    pub domains: Vec<D>,
+    /// The uses in the current window at the time of the export.
+    pub uses: i16,
+    pub cooldown: Option<chrono::DateTime<chrono::Utc>>,
+    /// Whether the key was permanently disabled, e.g. because it was deleted.
+    pub disabled: bool,
+}
 
-            for (idx, domain) in domains.iter().enumerate() {
-                if idx == 0 {
-                    builder.push("(");
-                } else {
-                    builder.push(" or ");
-                }
-                builder
-                    .push("domains @> ")
-                    .push_bind(sqlx::types::Json(vec![domain]));
-            }
-            builder.push(")")
+/// Determines which of the eligible keys is picked by [`KeyPoolStorage::acquire_key`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AcquireStrategy {
+    /// Pick the key with the fewest uses in the current minute.
+    #[default]
+    LeastUses,
+    /// Pick the key which hasn't been used for the longest time.
+    LeastRecentlyUsed,
+    /// Pick any eligible key at random.
+    Random,
+}
+
+impl AcquireStrategy {
+    fn order_by(self) -> &'static str {
+        match self {
+            Self::LeastUses => "uses asc",
+            Self::LeastRecentlyUsed => "last_used asc",
+            Self::Random => "random()",
         }
-    };
+    }
+}
+
+/// Determines how concurrent acquisitions of the same key are prevented.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Isolation {
+    /// Run the acquisition in a `repeatable read` transaction and retry it on serialisation
+    /// failures.
+    #[default]
+    RepeatableRead,
+    /// Lock the acquired rows with `for update skip locked` in the default isolation level. This
+    /// avoids the `set transaction` statement, which doesn't work well behind connection poolers
+    /// like PgBouncer in transaction mode, and never needs to retry on serialisation failures.
+    SkipLocked,
+}
+
+/// Determines how the uses of a key are counted towards the per minute limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UsageWindow {
+    /// Count the uses since the start of the current minute. All keys are refreshed at the
+    /// same time, so the limit can be exceeded in the seconds around a minute boundary.
+    #[default]
+    Minute,
+    /// Count the uses during the last 60 seconds. This records a timestamp for every use.
+    Sliding,
 }
 
 #[derive(Debug, Clone, FromRow)]
@@ -95,9 +133,23 @@ where
 {
     pool: PgPool,
     limit: i16,
+    strategy: AcquireStrategy,
+    window: UsageWindow,
+    isolation: Isolation,
+    now_fn: Option<NowFn>,
     _phantom: std::marker::PhantomData<D>,
 }
 
+/// A replacement for the time of the database. See [`PgKeyPoolStorage::with_now_fn`].
+#[derive(Clone)]
+struct NowFn(Arc<dyn Fn() -> chrono::DateTime<chrono::Utc> + Send + Sync>);
+
+impl std::fmt::Debug for NowFn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("NowFn").finish_non_exhaustive()
+    }
+}
+
 impl<D> ApiKey for PgKey<D>
 where
     D: PgKeyDomain,
@@ -113,6 +165,11 @@ where
     fn id(&self) -> Self::IdType {
         self.id
     }
+
+    #[inline(always)]
+    fn access_level(&self) -> Option<i16> {
+        self.access_level
+    }
 }
 
 impl<D> PgKeyPoolStorage<D>
@@ -123,10 +180,304 @@ where
         Self {
             pool,
             limit,
+            strategy: AcquireStrategy::default(),
+            window: UsageWindow::default(),
+            isolation: Isolation::default(),
+            now_fn: None,
             _phantom: Default::default(),
         }
     }
 
+    /// Sets the strategy used when acquiring single keys. Bulk acquisition always spreads the
+    /// requests evenly across the available keys.
+    pub fn with_strategy(mut self, strategy: AcquireStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Sets how the uses of a key are counted towards the limit.
+    pub fn with_window(mut self, window: UsageWindow) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets how concurrent acquisitions of the same key are prevented.
+    pub fn with_isolation(mut self, isolation: Isolation) -> Self {
+        self.isolation = isolation;
+        self
+    }
+
+    /// Takes the current time from `now_fn` instead of the database, so that tests of time
+    /// sensitive behaviour like the usage windows and cooldowns can pin the time.
+    pub fn with_now_fn<F>(mut self, now_fn: F) -> Self
+    where
+        F: Fn() -> chrono::DateTime<chrono::Utc> + Send + Sync + 'static,
+    {
+        self.now_fn = Some(NowFn(Arc::new(now_fn)));
+        self
+    }
+
+    async fn begin(&self) -> Result<sqlx::Transaction<'static, Postgres>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        if self.isolation == Isolation::RepeatableRead {
+            sqlx::query("set transaction isolation level repeatable read")
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        Ok(tx)
+    }
+
+    /// Whether any key matching the selector could serve `uses` requests right now. Keys which
+    /// are locked by concurrent transactions are invisible to [`Isolation::SkipLocked`] queries,
+    /// so not finding a key doesn't mean that all keys are exhausted.
+    async fn any_key_eligible(
+        &self,
+        selector: &KeySelector<PgKey<D>, D>,
+        uses: i16,
+    ) -> Result<bool, sqlx::Error> {
+        let mut qb = QueryBuilder::new(format!(
+            "select exists(select 1 from api_keys where (cooldown is null or {} >= cooldown) and ",
+            self.now()
+        ));
+        qb.push(self.current_uses())
+            .push(" <= ")
+            .push_bind(self.limit - uses)
+            .push(" and ");
+        self.build_predicate(&mut qb, selector);
+        qb.push(")");
+
+        qb.build_query_scalar().fetch_one(&self.pool).await
+    }
+
+    /// SQL expression for the number of uses a key has in the current window.
+    fn current_uses(&self) -> String {
+        let now = self.now();
+        match self.window {
+            UsageWindow::Minute => format!(
+                "(case when last_used < date_trunc('minute', {now}) then 0::int2 else uses end)"
+            ),
+            UsageWindow::Sliding => format!(
+                "(select count(*) from unnest(use_log) as t where t > {now} - interval '1 minute')::int2"
+            ),
+        }
+    }
+
+    /// SQL expression for the current time.
+    fn now(&self) -> String {
+        match &self.now_fn {
+            Some(now_fn) => format!("'{}'::timestamptz", (now_fn.0)().to_rfc3339()),
+            None => "now()".to_owned(),
+        }
+    }
+
+    #[inline(always)]
+    fn build_predicate<'b>(
+        &self,
+        builder: &mut QueryBuilder<'b, Postgres>,
+        selector: &'b KeySelector<PgKey<D>, D>,
+    ) {
+        match selector {
+            KeySelector::Id(id) => builder.push("id=").push_bind(id),
+            KeySelector::UserId(user_id) => builder.push("user_id=").push_bind(user_id),
+            KeySelector::Key(key) => builder.push("key=").push_bind(key),
+            KeySelector::Has(domains) => builder
+                .push("domains @> ")
+                .push_bind(sqlx::types::Json(domains)),
+            KeySelector::HasKind(kind) => builder
+                .push("domains @> jsonb_build_array(jsonb_build_object('type', ")
+                .push_bind(*kind)
+                .push("::text))"),
+            KeySelector::OneOf(domains) => {
+                if domains.is_empty() {
+                    builder.push("false");
+                    return;
+                }
+
+                for (idx, domain) in domains.iter().enumerate() {
+                    if idx == 0 {
+                        builder.push("(");
+                    } else {
+                        builder.push(" or ");
+                    }
+                    builder
+                        .push("domains @> ")
+                        .push_bind(sqlx::types::Json(vec![domain]));
+                }
+                builder.push(")")
+            }
+            KeySelector::WithBudget {
+                selector,
+                min_remaining,
+            } => {
+                builder.push("(");
+                self.build_predicate(builder, selector);
+                builder
+                    .push(" and ")
+                    .push(self.current_uses())
+                    .push(" <= ")
+                    .push_bind(self.limit - min_remaining)
+                    .push(")")
+            }
+            KeySelector::WithAccessLevel {
+                selector,
+                min_level,
+            } => {
+                builder.push("(");
+                self.build_predicate(builder, selector);
+                builder
+                    .push(" and access_level >= ")
+                    .push_bind(*min_level)
+                    .push(")")
+            }
+        };
+    }
+
+    /// Splits off the budget of [`KeySelector::WithBudget`] selectors, returning the selector
+    /// without budget and the number of uses a key needs to have left to be selected.
+    fn split_budget(selector: &KeySelector<PgKey<D>, D>) -> (&KeySelector<PgKey<D>, D>, i16) {
+        match selector {
+            KeySelector::WithBudget {
+                selector,
+                min_remaining,
+            } => {
+                let (selector, needed) = Self::split_budget(selector);
+                (selector, std::cmp::max(needed, *min_remaining))
+            }
+            selector => (selector, 1),
+        }
+    }
+
+    /// Whether the selector matches any key which isn't flagged as permanently unusable,
+    /// regardless of its uses and temporary cooldowns.
+    async fn any_key_matches(
+        &self,
+        selector: &KeySelector<PgKey<D>, D>,
+    ) -> Result<bool, PgStorageError<D>> {
+        let mut qb = QueryBuilder::new(
+            "select exists(select 1 from api_keys where (cooldown is null or cooldown < 'infinity') and ",
+        );
+        self.build_predicate(&mut qb, Self::split_budget(selector).0);
+        qb.push(")");
+
+        Ok(qb.build_query_scalar().fetch_one(&self.pool).await?)
+    }
+
+    /// Acquires a key with at least `uses` uses left in the current minute and adds them to
+    /// the key's count. Also returns the selector which matched the key.
+    async fn acquire_key_uses(
+        &self,
+        mut selector: KeySelector<PgKey<D>, D>,
+        uses: i16,
+    ) -> Result<(PgKey<D>, KeySelector<PgKey<D>, D>), PgStorageError<D>> {
+        let mut matched = false;
+        loop {
+            let attempt = async {
+                let mut tx = self.begin().await?;
+                let now = self.now();
+
+                let mut qb = QueryBuilder::new(indoc::indoc! {
+                    r#"
+                    with key as (
+                        select id, uses from (
+                            select id, "#
+                });
+                qb.push(self.current_uses());
+                qb.push(format!(
+                    indoc::indoc! {
+                        r#" as uses, last_used from api_keys
+                                where (cooldown is null or {} >= cooldown)
+                                    and "#
+                    },
+                    now
+                ));
+                qb.push(self.current_uses())
+                    .push(" <= ")
+                    .push_bind(self.limit - uses)
+                    .push(" and ");
+                self.build_predicate(&mut qb, &selector);
+
+                qb.push("\n    ) as candidates\n    order by ")
+                    .push(self.strategy.order_by())
+                    .push(" limit 1");
+                if self.isolation == Isolation::SkipLocked {
+                    qb.push(" for update skip locked");
+                }
+                qb.push("\n)");
+
+                qb.push("\nupdate api_keys set\n    uses = key.uses + ")
+                    .push_bind(uses);
+                if self.window == UsageWindow::Sliding {
+                    qb.push(format!(",\n    use_log = array(select t from unnest(use_log) as t where t > {now} - interval '1 minute') || array_fill({now}, array["))
+                        .push_bind(uses as i32)
+                        .push("])");
+                }
+
+                qb.push(format!(
+                    indoc::indoc! {
+                        ",
+                            cooldown = null,
+                            flag = null,
+                            last_used = {}
+                        from key where 
+                            api_keys.id=key.id and key.uses <= "
+                    },
+                    now
+                ));
+
+                qb.push_bind(self.limit - uses);
+
+                qb.push(indoc::indoc! { "
+                    \nreturning
+                        api_keys.id,
+                        api_keys.user_id,
+                        api_keys.key,
+                        api_keys.uses,
+                        api_keys.domains,
+                        api_keys.access_level,
+                        api_keys.last_used as acquired_at"
+                });
+
+                let key = qb.build_query_as().fetch_optional(&mut *tx).await?;
+
+                tx.commit().await?;
+
+                let contended = key.is_none()
+                    && self.isolation == Isolation::SkipLocked
+                    && self.any_key_eligible(&selector, uses).await?;
+
+                Result::<_, sqlx::Error>::Ok((key, contended))
+            }
+            .await;
+
+            match attempt {
+                Ok((Some(result), _)) => return Ok((result, selector)),
+                Ok((None, true)) => random_sleep().await,
+                Ok((None, false)) => {
+                    matched |= self.any_key_matches(&selector).await?;
+                    selector = match selector.fallback() {
+                        Some(fallback) => fallback,
+                        None if matched => return Err(PgStorageError::AllKeysExhausted(selector)),
+                        None => return Err(PgStorageError::NoMatchingKey(selector)),
+                    };
+                }
+                Err(error) => {
+                    if let Some(db_error) = error.as_database_error() {
+                        let pg_error: &sqlx::postgres::PgDatabaseError = db_error.downcast_ref();
+                        if pg_error.code() == "40001" {
+                            random_sleep().await;
+                        } else {
+                            return Err(error.into());
+                        }
+                    } else {
+                        return Err(error.into());
+                    }
+                }
+            }
+        }
+    }
+
     pub async fn initialise(&self) -> Result<(), PgStorageError<D>> {
         sqlx::query(indoc! {r#"
             CREATE TABLE IF NOT EXISTS api_keys (
@@ -138,12 +489,31 @@ where
                 last_used timestamptz not null default now(),
                 flag int2,
                 cooldown timestamptz,
+                selections jsonb,
+                access_level int2,
+                use_log timestamptz[] not null default '{}',
                 constraint "uq:api_keys.key" UNIQUE(key)
             )"#
         })
         .execute(&self.pool)
         .await?;
 
+        // tables created by older versions might lack some of the columns
+        sqlx::query(indoc! {r#"
+            ALTER TABLE api_keys
+                ADD COLUMN IF NOT EXISTS uses int2 not null default 0,
+                ADD COLUMN IF NOT EXISTS domains jsonb not null default '{}'::jsonb,
+                ADD COLUMN IF NOT EXISTS last_used timestamptz not null default now(),
+                ADD COLUMN IF NOT EXISTS flag int2,
+                ADD COLUMN IF NOT EXISTS cooldown timestamptz,
+                ADD COLUMN IF NOT EXISTS selections jsonb,
+                ADD COLUMN IF NOT EXISTS access_level int2,
+                ADD COLUMN IF NOT EXISTS use_log timestamptz[] not null default '{}'
+            "#
+        })
+        .execute(&self.pool)
+        .await?;
+
         sqlx::query(indoc! {r#"
             CREATE INDEX IF NOT EXISTS "idx:api_keys.domains" ON api_keys USING GIN(domains jsonb_path_ops)
         "#})
@@ -180,6 +550,218 @@ where
 
         Ok(())
     }
+
+    /// Stores the access level of the key (see [`torn_api::key::access_level`]), as reported by
+    /// the `key` endpoint's `info` selection. Keys with a stored access level can stand in for
+    /// keys which lack the access a request needs.
+    pub async fn set_key_access_level(
+        &self,
+        key_id: i32,
+        access_level: i16,
+    ) -> Result<(), PgStorageError<D>> {
+        let result = sqlx::query("update api_keys set access_level=$2 where id=$1")
+            .bind(key_id)
+            .bind(access_level)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            Err(PgStorageError::KeyNotFound(KeySelector::Id(key_id)))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Stores the selections which the key is permitted to access, as reported by the `key`
+    /// endpoint's `info` selection.
+    pub async fn set_key_selections(
+        &self,
+        key_id: i32,
+        selections: &torn_api::key::Selections,
+    ) -> Result<(), PgStorageError<D>> {
+        let result = sqlx::query("update api_keys set selections=$2 where id=$1")
+            .bind(key_id)
+            .bind(sqlx::types::Json(selections))
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            Err(PgStorageError::KeyNotFound(KeySelector::Id(key_id)))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks whether the key may access the selection of the given category, e.g.
+    /// `("faction", "donations")`. Keys whose selections were never stored through
+    /// [`Self::set_key_selections`] are assumed to be able to serve any selection.
+    pub async fn key_can_serve(
+        &self,
+        key_id: i32,
+        category: &str,
+        selection: &str,
+    ) -> Result<bool, PgStorageError<D>> {
+        sqlx::query_scalar(
+            "select coalesce((selections -> $2::text) ? $3::text, selections is null) from api_keys where id=$1",
+        )
+        .bind(key_id)
+        .bind(category)
+        .bind(selection)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(PgStorageError::KeyNotFound(KeySelector::Id(key_id)))
+    }
+
+    /// The earliest time at which a key matching the selector, or one of its fallbacks, can be
+    /// acquired. This is either now, the end of a key's cooldown or the time at which the uses
+    /// of a rate limited key expire. Returns `None` if no usable key matches the selector.
+    pub async fn next_available_at<S>(
+        &self,
+        selector: S,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, PgStorageError<D>>
+    where
+        S: IntoSelector<PgKey<D>, D>,
+    {
+        let mut selector = Some(selector.into_selector());
+        let mut earliest: Option<chrono::DateTime<chrono::Utc>> = None;
+
+        while let Some(current) = selector {
+            let (inner, needed) = Self::split_budget(&current);
+            let uses = self.current_uses();
+            let now = self.now();
+
+            let mut qb = QueryBuilder::new(format!(
+                "select min(case when cooldown > {now} then cooldown when "
+            ));
+            qb.push(&uses)
+                .push(" + ")
+                .push_bind(needed)
+                .push(" <= ")
+                .push_bind(self.limit)
+                .push(format!(" then {now} else "));
+            match self.window {
+                UsageWindow::Minute => {
+                    qb.push(format!("date_trunc('minute', {now}) + interval '1 minute'"));
+                }
+                UsageWindow::Sliding => {
+                    // the uses expire one after the other, so the key becomes available once
+                    // enough of the oldest uses fall out of the window
+                    qb.push(format!(
+                        "(select t from unnest(use_log) as t where t > {now} - interval '1 minute' order by t offset greatest(",
+                    ))
+                    .push(&uses)
+                    .push(" + ")
+                    .push_bind(needed)
+                    .push(" - ")
+                    .push_bind(self.limit)
+                    .push(" - 1, 0) limit 1) + interval '1 minute'");
+                }
+            }
+            qb.push(" end) from api_keys where (cooldown is null or cooldown < 'infinity') and ");
+            self.build_predicate(&mut qb, inner);
+
+            let next: Option<chrono::DateTime<chrono::Utc>> =
+                qb.build_query_scalar().fetch_one(&self.pool).await?;
+            earliest = match (earliest, next) {
+                (Some(a), Some(b)) => Some(std::cmp::min(a, b)),
+                (a, b) => a.or(b),
+            };
+
+            selector = current.fallback();
+        }
+
+        Ok(earliest)
+    }
+
+    /// Reads all keys of the pool including their uses and cooldowns.
+    pub async fn export_keys(&self) -> Result<Vec<KeyRecord<D>>, PgStorageError<D>> {
+        let mut qb = QueryBuilder::new("select user_id, key, domains, ");
+        qb.push(self.current_uses());
+        qb.push(indoc! {r#" as uses,
+                nullif(cooldown, 'infinity') as cooldown,
+                coalesce(cooldown = 'infinity', false) as disabled
+            from api_keys order by id"#
+        });
+
+        Ok(qb.build_query_as().fetch_all(&self.pool).await?)
+    }
+
+    /// Stores keys which were read through [`Self::export_keys`], e.g. from another database.
+    /// Keys which already exist take over the uses and cooldown of the record and gain its
+    /// domains.
+    pub async fn import_keys<I>(&self, records: I) -> Result<Vec<PgKey<D>>, PgStorageError<D>>
+    where
+        I: IntoIterator<Item = KeyRecord<D>>,
+    {
+        // a single insert can't touch the same row twice, so later records replace earlier ones
+        let mut unique: Vec<KeyRecord<D>> = Vec::new();
+        for record in records {
+            match unique.iter_mut().find(|r| r.key == record.key) {
+                Some(existing) => *existing = record,
+                None => unique.push(record),
+            }
+        }
+
+        let query = format!(
+            indoc! {r#"
+            insert into api_keys(user_id, key, domains, uses, cooldown, use_log, last_used)
+                select
+                    user_id,
+                    key,
+                    domains,
+                    uses,
+                    case when disabled then 'infinity' else cooldown end,
+                    array_fill({now}, array[uses::int4]),
+                    {now}
+                from unnest($1::int4[], $2::text[], $3::jsonb[], $4::int2[], $5::timestamptz[], $6::bool[])
+                    as t(user_id, key, domains, uses, cooldown, disabled)
+            on conflict on constraint "uq:api_keys.key" do update set
+                domains = __unique_jsonb_array(excluded.domains || api_keys.domains),
+                uses = excluded.uses,
+                cooldown = excluded.cooldown,
+                use_log = excluded.use_log,
+                last_used = excluded.last_used
+            returning *
+        "#},
+            now = self.now()
+        );
+
+        Ok(sqlx::query_as(&query)
+            .bind(unique.iter().map(|r| r.user_id).collect::<Vec<_>>())
+            .bind(unique.iter().map(|r| r.key.clone()).collect::<Vec<_>>())
+            .bind(
+                unique
+                    .iter()
+                    .map(|r| sqlx::types::Json(&r.domains))
+                    .collect::<Vec<_>>(),
+            )
+            .bind(unique.iter().map(|r| r.uses).collect::<Vec<_>>())
+            .bind(unique.iter().map(|r| r.cooldown).collect::<Vec<_>>())
+            .bind(unique.iter().map(|r| r.disabled).collect::<Vec<_>>())
+            .fetch_all(&self.pool)
+            .await?)
+    }
+
+    /// Sets the uses of the selected keys back to zero. Returns the number of affected keys.
+    pub async fn reset_usage<S>(&self, selector: S) -> Result<u64, PgStorageError<D>>
+    where
+        S: IntoSelector<PgKey<D>, D>,
+    {
+        let selector = selector.into_selector();
+
+        let mut qb = QueryBuilder::new("update api_keys set uses=0, use_log='{}' where ");
+        self.build_predicate(&mut qb, &selector);
+
+        Ok(qb.build().execute(&self.pool).await?.rows_affected())
+    }
+
+    /// Sets the uses of every key in the pool back to zero. Returns the number of affected keys.
+    pub async fn reset_all_usage(&self) -> Result<u64, PgStorageError<D>> {
+        Ok(sqlx::query("update api_keys set uses=0, use_log='{}'")
+            .execute(&self.pool)
+            .await?
+            .rows_affected())
+    }
 }
 
 #[cfg(feature = "tokio-runtime")]
@@ -210,98 +792,18 @@ where
     where
         S: IntoSelector<Self::Key, Self::Domain>,
     {
-        let selector = selector.into_selector();
-        loop {
-            let attempt = async {
-                let mut tx = self.pool.begin().await?;
-
-                sqlx::query("set transaction isolation level repeatable read")
-                    .execute(&mut *tx)
-                    .await?;
-
-                let mut qb = QueryBuilder::new(indoc::indoc! {
-                    r#"
-                    with key as (
-                        select 
-                            id,
-                            0::int2 as uses
-                        from api_keys where last_used < date_trunc('minute', now()) 
-                            and (cooldown is null or now() >= cooldown)
-                            and "#
-                });
-
-                build_predicate(&mut qb, &selector);
-
-                qb.push(indoc::indoc! {
-                    "
-                    \n    union (
-                            select id, uses from api_keys 
-                            where last_used >= date_trunc('minute', now()) 
-                                and (cooldown is null or now() >= cooldown) 
-                                and "
-                });
-
-                build_predicate(&mut qb, &selector);
-
-                qb.push(indoc::indoc! {
-                    "
-                    \n        order by uses asc limit 1
-                        )
-                        order by uses asc limit 1
-                    )
-                    update api_keys set
-                        uses = key.uses + 1,
-                        cooldown = null,
-                        flag = null,
-                        last_used = now()
-                    from key where 
-                        api_keys.id=key.id and key.uses < "
-                });
-
-                qb.push_bind(self.limit);
-
-                qb.push(indoc::indoc! { "
-                    \nreturning
-                        api_keys.id,
-                        api_keys.user_id,
-                        api_keys.key,
-                        api_keys.uses,
-                        api_keys.domains"
-                });
-
-                let key = qb.build_query_as().fetch_optional(&mut *tx).await?;
-
-                tx.commit().await?;
-
-                Result::<Option<Self::Key>, sqlx::Error>::Ok(key)
-            }
-            .await;
+        let (key, _) = self.acquire_key_uses(selector.into_selector(), 1).await?;
+        Ok(key)
+    }
 
-            match attempt {
-                Ok(Some(result)) => return Ok(result),
-                Ok(None) => {
-                    return self
-                        .acquire_key(
-                            selector
-                                .fallback()
-                                .ok_or_else(|| PgStorageError::Unavailable(selector))?,
-                        )
-                        .await
-                }
-                Err(error) => {
-                    if let Some(db_error) = error.as_database_error() {
-                        let pg_error: &sqlx::postgres::PgDatabaseError = db_error.downcast_ref();
-                        if pg_error.code() == "40001" {
-                            random_sleep().await;
-                        } else {
-                            return Err(error.into());
-                        }
-                    } else {
-                        return Err(error.into());
-                    }
-                }
-            }
-        }
+    async fn acquire_key_matched<S>(
+        &self,
+        selector: S,
+    ) -> Result<(Self::Key, KeySelector<Self::Key, Self::Domain>), Self::Error>
+    where
+        S: IntoSelector<Self::Key, Self::Domain>,
+    {
+        self.acquire_key_uses(selector.into_selector(), 1).await
     }
 
     async fn acquire_many_keys<S>(
@@ -315,57 +817,53 @@ where
         let selector = selector.into_selector();
         loop {
             let attempt = async {
-                let mut tx = self.pool.begin().await?;
-
-                sqlx::query("set transaction isolation level repeatable read")
-                    .execute(&mut *tx)
-                    .await?;
-
-                let mut qb = QueryBuilder::new(indoc::indoc! {
-                    r#"select
-                        id,
-                        user_id,
-                        key,
-                        0::int2 as uses,
-                        domains
-                    from api_keys where last_used < date_trunc('minute', now())
-                        and (cooldown is null or now() >= cooldown)
-                        and "#
-                });
-                build_predicate(&mut qb, &selector);
-                qb.push(indoc::indoc! {
-                    "
-                    \nunion
-                    select
-                        id,
-                        user_id,
-                        key,
-                        uses,
-                        domains
-                    from api_keys where last_used >= date_trunc('minute', now())
-                        and (cooldown is null or now() >= cooldown)
-                        and "
-                });
-                build_predicate(&mut qb, &selector);
+                let mut tx = self.begin().await?;
+                let now = self.now();
+
+                let mut qb = QueryBuilder::new("select id, user_id, key, ");
+                qb.push(self.current_uses());
+                qb.push(format!(
+                    indoc::indoc! {
+                        r#" as uses, domains, access_level
+                        from api_keys where (cooldown is null or {} >= cooldown)
+                            and "#
+                    },
+                    now
+                ));
+                // keys which were forced past the limit have no uses to spare
+                qb.push(self.current_uses())
+                    .push(" < ")
+                    .push_bind(self.limit)
+                    .push(" and ");
+                self.build_predicate(&mut qb, &selector);
                 qb.push("\norder by uses limit ");
                 qb.push_bind(self.limit);
+                if self.isolation == Isolation::SkipLocked {
+                    qb.push(" for update skip locked");
+                }
 
                 let mut keys: Vec<Self::Key> = qb.build_query_as().fetch_all(&mut *tx).await?;
 
                 if keys.is_empty() {
                     tx.commit().await?;
+                    if self.isolation == Isolation::SkipLocked
+                        && self.any_key_eligible(&selector, 1).await?
+                    {
+                        return Ok(Some(None));
+                    }
                     return Ok(None);
                 }
 
-                keys.sort_unstable_by(|k1, k2| k1.uses.cmp(&k2.uses));
+                keys.sort_unstable_by_key(|k| k.uses);
+                let previous: Vec<_> = keys.iter().map(|k| k.uses).collect();
 
                 let mut result = Vec::with_capacity(number as usize);
                 let (max, rest) = keys.split_last_mut().unwrap();
                 for key in rest {
-                    let available = max.uses - key.uses;
+                    let available = std::cmp::min(max.uses, self.limit) - key.uses;
                     let using = std::cmp::min(available, (number as i16) - (result.len() as i16));
                     key.uses += using;
-                    result.extend(std::iter::repeat(key.clone()).take(using as usize));
+                    result.extend(std::iter::repeat_n(key.clone(), using as usize));
 
                     if result.len() == number as usize {
                         break;
@@ -373,7 +871,7 @@ where
                 }
 
                 while result.len() < (number as usize) {
-                    if keys[0].uses == self.limit {
+                    if keys[0].uses >= self.limit {
                         break;
                     }
 
@@ -383,37 +881,65 @@ where
                     result.extend_from_slice(slice);
                 }
 
-                sqlx::query(indoc! {r#"
-                    update api_keys set
-                        uses = tmp.uses,
-                        cooldown = null,
-                        flag = null,
-                        last_used = now()
-                    from (select unnest($1::int4[]) as id, unnest($2::int2[]) as uses) as tmp
-                    where api_keys.id = tmp.id
-                "#})
-                .bind(keys.iter().map(|k| k.id).collect::<Vec<_>>())
-                .bind(keys.iter().map(|k| k.uses).collect::<Vec<_>>())
-                .execute(&mut *tx)
-                .await?;
+                if result.is_empty() {
+                    tx.commit().await?;
+                    return Ok(None);
+                }
+
+                let log = match self.window {
+                    UsageWindow::Minute => String::new(),
+                    UsageWindow::Sliding => format!(
+                        "use_log = array(select t from unnest(use_log) as t where t > {now} - interval '1 minute') || array_fill({now}, array[(tmp.uses - tmp.previous)::int4]),"
+                    ),
+                };
+                let query = format!(
+                    indoc! {r#"
+                        update api_keys set
+                            uses = tmp.uses,
+                            {}
+                            cooldown = null,
+                            flag = null,
+                            last_used = {}
+                        from (
+                            select
+                                unnest($1::int4[]) as id,
+                                unnest($2::int2[]) as uses,
+                                unnest($3::int2[]) as previous
+                        ) as tmp
+                        where api_keys.id = tmp.id
+                    "#},
+                    log, now
+                );
+
+                sqlx::query(&query)
+                    .bind(keys.iter().map(|k| k.id).collect::<Vec<_>>())
+                    .bind(keys.iter().map(|k| k.uses).collect::<Vec<_>>())
+                    .bind(previous)
+                    .execute(&mut *tx)
+                    .await?;
 
                 tx.commit().await?;
 
-                Result::<Option<Vec<Self::Key>>, sqlx::Error>::Ok(Some(result))
+                // `Some(None)` means that all eligible keys were locked by other transactions
+                Result::<Option<Option<Vec<Self::Key>>>, sqlx::Error>::Ok(Some(Some(result)))
             }
             .await;
 
             match attempt {
-                Ok(Some(result)) => return Ok(result),
+                Ok(Some(Some(result))) => return Ok(result),
+                Ok(Some(None)) => random_sleep().await,
                 Ok(None) => {
-                    return self
-                        .acquire_many_keys(
-                            selector
-                                .fallback()
-                                .ok_or_else(|| Self::Error::Unavailable(selector))?,
-                            number,
-                        )
-                        .await
+                    let matched = self.any_key_matches(&selector).await?;
+                    return match selector.fallback() {
+                        Some(fallback) => match self.acquire_many_keys(fallback, number).await {
+                            Err(PgStorageError::NoMatchingKey(selector)) if matched => {
+                                Err(PgStorageError::AllKeysExhausted(selector))
+                            }
+                            result => result,
+                        },
+                        None if matched => Err(PgStorageError::AllKeysExhausted(selector)),
+                        None => Err(PgStorageError::NoMatchingKey(selector)),
+                    };
                 }
                 Err(error) => {
                     if let Some(db_error) = error.as_database_error() {
@@ -431,10 +957,39 @@ where
         }
     }
 
+    async fn force_acquire_key(&self, id: i32) -> Result<Self::Key, Self::Error> {
+        let now = self.now();
+        let log = match self.window {
+            UsageWindow::Minute => String::new(),
+            UsageWindow::Sliding => format!(
+                "use_log = array(select t from unnest(use_log) as t where t > {now} - interval '1 minute') || {now},"
+            ),
+        };
+        let query = format!(
+            indoc! {r#"
+                update api_keys set
+                    uses = {} + 1,
+                    {}
+                    last_used = {}
+                where id=$1
+                returning id, user_id, key, uses, domains, access_level
+            "#},
+            self.current_uses(),
+            log,
+            now
+        );
+
+        sqlx::query_as(&query)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(PgStorageError::KeyNotFound(KeySelector::Id(id)))
+    }
+
     async fn flag_key(&self, key: Self::Key, code: u8) -> Result<bool, Self::Error> {
         match code {
-            2 | 10 | 13 => {
-                // invalid key, owner fedded or owner inactive
+            2 | 13 => {
+                // invalid key or owner inactive
                 sqlx::query(
                     "update api_keys set cooldown='infinity'::timestamptz, flag=$1 where id=$2",
                 )
@@ -444,12 +999,25 @@ where
                 .await?;
                 Ok(true)
             }
+            10 => {
+                // owner fedded. Federal jail is temporary, so the key is retried once the
+                // cooldown has passed and flagged again if the owner is still fedded
+                sqlx::query(&format!(
+                    "update api_keys set cooldown={} + interval '6 hours', flag=10 where id=$1",
+                    self.now()
+                ))
+                .bind(key.id)
+                .execute(&self.pool)
+                .await?;
+                Ok(true)
+            }
             5 => {
                 // too many requests
-                sqlx::query(
-                    "update api_keys set cooldown=date_trunc('min', now()) + interval '1 min', \
+                sqlx::query(&format!(
+                    "update api_keys set cooldown=date_trunc('min', {}) + interval '1 min', \
                      flag=5 where id=$1",
-                )
+                    self.now()
+                ))
                 .bind(key.id)
                 .execute(&self.pool)
                 .await?;
@@ -457,24 +1025,31 @@ where
             }
             8 => {
                 // IP block
-                sqlx::query("update api_keys set cooldown=now() + interval '5 min', flag=8")
-                    .execute(&self.pool)
-                    .await?;
+                sqlx::query(&format!(
+                    "update api_keys set cooldown={} + interval '5 min', flag=8",
+                    self.now()
+                ))
+                .execute(&self.pool)
+                .await?;
                 Ok(false)
             }
             9 => {
                 // API disabled
-                sqlx::query("update api_keys set cooldown=now() + interval '1 min', flag=9")
-                    .execute(&self.pool)
-                    .await?;
+                sqlx::query(&format!(
+                    "update api_keys set cooldown={} + interval '1 min', flag=9",
+                    self.now()
+                ))
+                .execute(&self.pool)
+                .await?;
                 Ok(false)
             }
             14 => {
                 // daily read limit reached
-                sqlx::query(
-                    "update api_keys set cooldown=date_trunc('day', now()) + interval '1 day', \
+                sqlx::query(&format!(
+                    "update api_keys set cooldown=date_trunc('day', {}) + interval '1 day', \
                      flag=14 where id=$1",
-                )
+                    self.now()
+                ))
                 .bind(key.id)
                 .execute(&self.pool)
                 .await?;
@@ -503,6 +1078,46 @@ where
         .map_err(Into::into)
     }
 
+    async fn store_keys<I>(&self, entries: I) -> Result<Vec<Self::Key>, Self::Error>
+    where
+        I: IntoIterator<Item = (i32, String, Vec<D>)> + Send,
+        I::IntoIter: Send,
+    {
+        let mut user_ids = Vec::new();
+        let mut keys: Vec<String> = Vec::new();
+        let mut domains: Vec<sqlx::types::Json<Vec<D>>> = Vec::new();
+
+        // a single insert can't touch the same row twice, so duplicates are merged beforehand
+        for (user_id, key, key_domains) in entries {
+            if let Some(idx) = keys.iter().position(|k| *k == key) {
+                let existing = &mut domains[idx].0;
+                for domain in key_domains {
+                    if !existing.contains(&domain) {
+                        existing.push(domain);
+                    }
+                }
+            } else {
+                user_ids.push(user_id);
+                keys.push(key);
+                domains.push(sqlx::types::Json(key_domains));
+            }
+        }
+
+        sqlx::query_as(indoc! {r#"
+            insert into api_keys(user_id, key, domains)
+                select * from unnest($1::int4[], $2::text[], $3::jsonb[])
+            on conflict on constraint "uq:api_keys.key" do update set
+                domains = __unique_jsonb_array(excluded.domains || api_keys.domains)
+            returning *
+        "#})
+        .bind(user_ids)
+        .bind(keys)
+        .bind(domains)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
     async fn read_key<S>(&self, selector: S) -> Result<Option<Self::Key>, Self::Error>
     where
         S: IntoSelector<Self::Key, Self::Domain>,
@@ -510,7 +1125,7 @@ where
         let selector = selector.into_selector();
 
         let mut qb = QueryBuilder::new("select * from api_keys where ");
-        build_predicate(&mut qb, &selector);
+        self.build_predicate(&mut qb, &selector);
 
         qb.build_query_as()
             .fetch_optional(&self.pool)
@@ -525,7 +1140,7 @@ where
         let selector = selector.into_selector();
 
         let mut qb = QueryBuilder::new("select * from api_keys where ");
-        build_predicate(&mut qb, &selector);
+        self.build_predicate(&mut qb, &selector);
 
         qb.build_query_as()
             .fetch_all(&self.pool)
@@ -540,7 +1155,7 @@ where
         let selector = selector.into_selector();
 
         let mut qb = QueryBuilder::new("delete from api_keys where ");
-        build_predicate(&mut qb, &selector);
+        self.build_predicate(&mut qb, &selector);
         qb.push(" returning *");
 
         qb.build_query_as()
@@ -549,6 +1164,22 @@ where
             .ok_or_else(|| PgStorageError::KeyNotFound(selector))
     }
 
+    async fn remove_keys<S>(&self, selector: S) -> Result<Vec<Self::Key>, Self::Error>
+    where
+        S: IntoSelector<Self::Key, Self::Domain>,
+    {
+        let selector = selector.into_selector();
+
+        let mut qb = QueryBuilder::new("delete from api_keys where ");
+        self.build_predicate(&mut qb, &selector);
+        qb.push(" returning *");
+
+        qb.build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Into::into)
+    }
+
     async fn add_domain_to_key<S>(&self, selector: S, domain: D) -> Result<Self::Key, Self::Error>
     where
         S: IntoSelector<Self::Key, Self::Domain>,
@@ -560,7 +1191,7 @@ where
         );
         qb.push_bind(sqlx::types::Json(domain));
         qb.push(")) where ");
-        build_predicate(&mut qb, &selector);
+        self.build_predicate(&mut qb, &selector);
         qb.push(" returning *");
 
         qb.build_query_as()
@@ -584,7 +1215,7 @@ where
         );
         qb.push_bind(sqlx::types::Json(domain));
         qb.push("), '[]'::jsonb) where ");
-        build_predicate(&mut qb, &selector);
+        self.build_predicate(&mut qb, &selector);
         qb.push(" returning *");
 
         qb.build_query_as()
@@ -606,7 +1237,7 @@ where
         let mut qb = QueryBuilder::new("update api_keys set domains = ");
         qb.push_bind(sqlx::types::Json(domains));
         qb.push(" where ");
-        build_predicate(&mut qb, &selector);
+        self.build_predicate(&mut qb, &selector);
         qb.push(" returning *");
 
         qb.build_query_as()
@@ -616,6 +1247,69 @@ where
     }
 }
 
+#[async_trait]
+impl<D> KeyReservationStorage for PgKeyPoolStorage<D>
+where
+    D: PgKeyDomain,
+{
+    async fn reserve_key<S>(&self, selector: S, uses: i16) -> Result<Self::Key, Self::Error>
+    where
+        S: IntoSelector<Self::Key, Self::Domain>,
+    {
+        let (key, _) = self
+            .acquire_key_uses(selector.into_selector(), uses)
+            .await?;
+        Ok(key)
+    }
+
+    async fn release_uses(&self, key: Self::Key, uses: i16) -> Result<(), Self::Error> {
+        match self.window {
+            // once the minute has passed the uses were reset anyway
+            UsageWindow::Minute => {
+                sqlx::query(&format!(
+                    indoc! {r#"
+                        update api_keys set uses = greatest(uses - $2, 0)
+                        where id=$1 and last_used >= date_trunc('minute', {})
+                    "#},
+                    self.now()
+                ))
+                .bind(key.id)
+                .bind(uses)
+                .execute(&self.pool)
+                .await?;
+            }
+            // the log may contain later uses by other acquirers, so only entries with the time of
+            // the reservation are removed. Once those left the window there's nothing to release
+            UsageWindow::Sliding => {
+                let Some(acquired_at) = key.acquired_at else {
+                    return Ok(());
+                };
+
+                sqlx::query(indoc! {r#"
+                    update api_keys set
+                        uses = greatest(uses - least($2, cardinality(array_positions(use_log, $3))), 0),
+                        use_log = array(
+                            select t from unnest(use_log) as t where t <> $3
+                            union all
+                            select $3 from generate_series(
+                                1, cardinality(array_positions(use_log, $3)) - $2
+                            )
+                            order by 1
+                        )
+                    where id=$1
+                "#})
+                .bind(key.id)
+                .bind(uses)
+                .bind(acquired_at)
+                .execute(&self.pool)
+                .await?;
+            }
+        };
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 pub(crate) mod test {
     use std::sync::Arc;
@@ -648,24 +1342,97 @@ pub(crate) mod test {
             .await
             .unwrap();
 
-        let storage = PgKeyPoolStorage::new(pool.clone(), 1000);
+        let storage = PgKeyPoolStorage::new(pool.clone(), 1000);
+        storage.initialise().await.unwrap();
+
+        let key = storage
+            .store_key(1, std::env::var("APIKEY").unwrap(), vec![Domain::All])
+            .await
+            .unwrap();
+
+        (storage, key)
+    }
+
+    #[sqlx::test]
+    async fn test_initialise(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+
+        if let Err(e) = storage.initialise().await {
+            panic!("Initialising key storage failed: {:?}", e);
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_initialise_migrates(pool: PgPool) {
+        sqlx::query("DROP TABLE IF EXISTS api_keys")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(indoc! {r#"
+            CREATE TABLE api_keys (
+                id serial primary key,
+                user_id int4 not null,
+                key char(16) not null,
+                uses int2 not null default 0,
+                domains jsonb not null default '{}'::jsonb,
+                last_used timestamptz not null default now(),
+                constraint "uq:api_keys.key" UNIQUE(key)
+            )"#
+        })
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let storage = PgKeyPoolStorage::<Domain>::new(pool, 1000);
+        storage.initialise().await.unwrap();
         storage.initialise().await.unwrap();
 
         let key = storage
-            .store_key(1, std::env::var("APIKEY").unwrap(), vec![Domain::All])
+            .store_key(1, "ABC".to_owned(), vec![Domain::All])
             .await
             .unwrap();
-
-        (storage, key)
+        assert!(storage.flag_key(key, 5).await.unwrap());
     }
 
     #[sqlx::test]
-    async fn test_initialise(pool: PgPool) {
-        let (storage, _) = setup(pool).await;
+    async fn key_selections(pool: PgPool) {
+        use std::collections::HashSet;
+        use torn_api::key::{FactionSelection, Selections, UserSelection};
 
-        if let Err(e) = storage.initialise().await {
-            panic!("Initialising key storage failed: {:?}", e);
-        }
+        let (storage, key) = setup(pool).await;
+
+        assert!(storage
+            .key_can_serve(key.id, "faction", "donations")
+            .await
+            .unwrap());
+
+        let selections = Selections {
+            user: HashSet::from([UserSelection::Basic, UserSelection::Profile]),
+            faction: HashSet::from([FactionSelection::Basic]),
+            company: HashSet::new(),
+            torn: HashSet::new(),
+            market: HashSet::new(),
+            property: HashSet::new(),
+            key: HashSet::new(),
+        };
+        storage
+            .set_key_selections(key.id, &selections)
+            .await
+            .unwrap();
+
+        assert!(storage
+            .key_can_serve(key.id, "user", "profile")
+            .await
+            .unwrap());
+        assert!(!storage
+            .key_can_serve(key.id, "faction", "donations")
+            .await
+            .unwrap());
+        assert!(!storage.key_can_serve(key.id, "v2", "basic").await.unwrap());
+        assert!(storage
+            .key_can_serve(key.id + 1, "user", "basic")
+            .await
+            .is_err());
     }
 
     #[sqlx::test]
@@ -690,6 +1457,41 @@ pub(crate) mod test {
         assert_eq!(key.domains.0.len(), 1);
     }
 
+    #[sqlx::test]
+    async fn test_store_keys(pool: PgPool) {
+        let (storage, key) = setup(pool).await;
+
+        let keys = storage
+            .store_keys([
+                (1, "ABC".to_owned(), vec![Domain::All]),
+                (2, "DEF".to_owned(), vec![Domain::User { id: 2 }]),
+                (2, "DEF".to_owned(), vec![Domain::Faction { id: 3 }]),
+                (1, key.key.clone(), vec![Domain::User { id: 1 }]),
+            ])
+            .await
+            .unwrap();
+
+        assert_eq!(keys.len(), 3);
+
+        let def = storage
+            .read_key(KeySelector::Key("DEF".to_owned()))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            def.domains.0,
+            vec![Domain::User { id: 2 }, Domain::Faction { id: 3 }]
+        );
+
+        let existing = storage
+            .read_key(KeySelector::Id(key.id))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(existing.domains.0.contains(&Domain::All));
+        assert!(existing.domains.0.contains(&Domain::User { id: 1 }));
+    }
+
     #[sqlx::test]
     async fn test_add_domain(pool: PgPool) {
         let (storage, key) = setup(pool).await;
@@ -788,6 +1590,46 @@ pub(crate) mod test {
         assert_eq!(keys.len(), 1);
     }
 
+    #[sqlx::test]
+    async fn test_remove_user_keys(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+        storage
+            .store_key(1, "ABC".to_owned(), vec![Domain::All])
+            .await
+            .unwrap();
+
+        let removed = storage.remove_keys(KeySelector::UserId(1)).await.unwrap();
+        assert_eq!(removed.len(), 2);
+
+        let removed = storage.remove_keys(KeySelector::UserId(1)).await.unwrap();
+        assert!(removed.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn query_by_kind(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+        storage
+            .store_key(1, "ABC".to_owned(), vec![Domain::Faction { id: 1 }])
+            .await
+            .unwrap();
+        storage
+            .store_key(2, "DEF".to_owned(), vec![Domain::Faction { id: 2 }])
+            .await
+            .unwrap();
+
+        let keys = storage
+            .read_keys(KeySelector::HasKind("faction"))
+            .await
+            .unwrap();
+        assert_eq!(keys.len(), 2);
+
+        let keys = storage
+            .read_keys(KeySelector::HasKind("guild"))
+            .await
+            .unwrap();
+        assert!(keys.is_empty());
+    }
+
     #[sqlx::test]
     async fn acquire_one(pool: PgPool) {
         let (storage, _) = setup(pool).await;
@@ -816,6 +1658,261 @@ pub(crate) mod test {
         }
     }
 
+    #[sqlx::test]
+    async fn least_recently_used(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+        let storage = storage.with_strategy(AcquireStrategy::LeastRecentlyUsed);
+        storage
+            .store_key(1, "ABC".to_owned(), vec![Domain::All])
+            .await
+            .unwrap();
+
+        let first = storage.acquire_key(Domain::All).await.unwrap();
+        let second = storage.acquire_key(Domain::All).await.unwrap();
+        let third = storage.acquire_key(Domain::All).await.unwrap();
+
+        assert_ne!(first.id, second.id);
+        assert_eq!(first.id, third.id);
+    }
+
+    #[sqlx::test]
+    async fn sliding_window(pool: PgPool) {
+        setup(pool.clone()).await;
+        let storage =
+            PgKeyPoolStorage::<Domain>::new(pool.clone(), 3).with_window(UsageWindow::Sliding);
+
+        storage.acquire_key(Domain::All).await.unwrap();
+        assert_eq!(
+            storage
+                .acquire_many_keys(Domain::All, 2)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(storage.acquire_key(Domain::All).await.is_err());
+
+        // the key becomes available again once its oldest use leaves the window
+        sqlx::query("update api_keys set use_log[1] = now() - interval '30 seconds'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let next = storage
+            .next_available_at(Domain::All)
+            .await
+            .unwrap()
+            .unwrap();
+        let expected = chrono::Utc::now() + chrono::Duration::seconds(30);
+        assert!((next - expected).num_seconds().abs() < 5);
+
+        // pretend that the first use happened more than a minute ago
+        sqlx::query("update api_keys set use_log[1] = now() - interval '61 seconds'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let key = storage.acquire_key(Domain::All).await.unwrap();
+        assert_eq!(key.uses, 3);
+        assert!(storage.acquire_key(Domain::All).await.is_err());
+
+        storage.release_uses(key, 1).await.unwrap();
+        let key = storage.force_acquire_key(1).await.unwrap();
+        assert_eq!(key.uses, 3);
+    }
+
+    #[sqlx::test]
+    async fn sliding_release(pool: PgPool) {
+        setup(pool.clone()).await;
+        let now = Arc::new(std::sync::Mutex::new(mid_minute()));
+        let clock = now.clone();
+        let storage = PgKeyPoolStorage::<Domain>::new(pool, 20)
+            .with_window(UsageWindow::Sliding)
+            .with_now_fn(move || *clock.lock().unwrap());
+        let advance = |seconds| *now.lock().unwrap() += chrono::Duration::seconds(seconds);
+        let uses = || async { storage.export_keys().await.unwrap()[0].uses };
+
+        // the reserved uses left the window before they were released, so the later uses of
+        // other acquirers must be kept
+        let reserved = storage.reserve_key(Domain::All, 10).await.unwrap();
+        advance(61);
+        for _ in 0..3 {
+            storage.acquire_key(Domain::All).await.unwrap();
+        }
+        storage.release_uses(reserved, 10).await.unwrap();
+        assert_eq!(uses().await, 3);
+
+        // only the uses of the reservation are released
+        let reserved = storage.reserve_key(Domain::All, 5).await.unwrap();
+        advance(1);
+        storage.acquire_key(Domain::All).await.unwrap();
+        storage.release_uses(reserved, 4).await.unwrap();
+        assert_eq!(uses().await, 5);
+    }
+
+    #[sqlx::test]
+    async fn reset_usage(pool: PgPool) {
+        let (storage, key) = setup(pool).await;
+        storage
+            .store_key(2, "BBBBBBBBBBBBBBBB".to_owned(), vec![Domain::All])
+            .await
+            .unwrap();
+        storage.acquire_many_keys(Domain::All, 4).await.unwrap();
+
+        assert_eq!(storage.reset_usage(key.selector()).await.unwrap(), 1);
+        let keys = storage.read_keys(Domain::All).await.unwrap();
+        let uses: Vec<_> = keys.iter().map(|k| (k.id, k.uses)).collect();
+        assert!(uses.contains(&(key.id, 0)));
+        assert!(uses.contains(&(2, 2)));
+
+        assert_eq!(storage.reset_all_usage().await.unwrap(), 2);
+        let keys = storage.read_keys(Domain::All).await.unwrap();
+        assert!(keys.iter().all(|k| k.uses == 0));
+    }
+
+    #[sqlx::test]
+    async fn with_budget(pool: PgPool) {
+        let (_, key) = setup(pool.clone()).await;
+        let storage = PgKeyPoolStorage::<Domain>::new(pool, 10);
+        let other = storage
+            .store_key(2, "BBBBBBBBBBBBBBBB".to_owned(), vec![Domain::All])
+            .await
+            .unwrap();
+        storage.reserve_key(key.selector(), 8).await.unwrap();
+
+        let selector = KeySelector::Has(vec![Domain::All]).with_budget(5);
+        let acquired = storage.acquire_key(selector.clone()).await.unwrap();
+        assert_eq!(acquired.id, other.id);
+
+        storage.reserve_key(other.selector(), 5).await.unwrap();
+        assert!(storage.acquire_key(selector).await.is_err());
+        assert_eq!(
+            storage
+                .read_keys(KeySelector::Has(vec![Domain::All]).with_budget(3))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[sqlx::test]
+    async fn acquire_matched(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+
+        let (_, matched) = storage.acquire_key_matched(Domain::All).await.unwrap();
+        assert!(matches!(matched, KeySelector::Has(domains) if domains == [Domain::All]));
+
+        let (_, matched) = storage
+            .acquire_key_matched(Domain::Guild { id: 1 })
+            .await
+            .unwrap();
+        assert!(matches!(matched, KeySelector::Has(domains) if domains == [Domain::All]));
+    }
+
+    #[sqlx::test]
+    async fn next_available_at(pool: PgPool) {
+        let (storage, key) = setup(pool).await;
+
+        let now = chrono::Utc::now();
+        let next = storage
+            .next_available_at(Domain::All)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!((next - now).num_seconds().abs() < 5);
+        assert_eq!(
+            storage
+                .next_available_at(Domain::Faction { id: 1 })
+                .await
+                .unwrap(),
+            None
+        );
+
+        storage.reserve_key(key.selector(), 995).await.unwrap();
+        let next = storage
+            .next_available_at(KeySelector::Has(vec![Domain::All]).with_budget(10))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(next.timestamp() % 60, 0);
+        assert!(next > chrono::Utc::now());
+
+        storage.flag_key(key.clone(), 8).await.unwrap();
+        let next = storage
+            .next_available_at(Domain::Guild { id: 1 })
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(next > chrono::Utc::now() + chrono::Duration::minutes(4));
+
+        storage.flag_key(key, 2).await.unwrap();
+        assert_eq!(storage.next_available_at(Domain::All).await.unwrap(), None);
+    }
+
+    #[sqlx::test]
+    async fn export_import(pool: PgPool) {
+        let (storage, key) = setup(pool.clone()).await;
+        let other = storage
+            .store_key(
+                2,
+                "BBBBBBBBBBBBBBBB".to_owned(),
+                vec![Domain::User { id: 2 }],
+            )
+            .await
+            .unwrap();
+        storage.reserve_key(key.selector(), 5).await.unwrap();
+        storage.flag_key(other, 2).await.unwrap();
+
+        let records = storage.export_keys().await.unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].uses, 5);
+        assert!(!records[0].disabled);
+        assert!(records[1].disabled);
+
+        let json = serde_json::to_string(&records).unwrap();
+        let records: Vec<KeyRecord<Domain>> = serde_json::from_str(&json).unwrap();
+
+        // stand-in for moving the keys to another database
+        sqlx::query("delete from api_keys")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let imported = storage.import_keys(records.clone()).await.unwrap();
+        assert_eq!(imported.len(), 2);
+        assert_eq!(storage.export_keys().await.unwrap(), records);
+
+        let key = storage.acquire_key(Domain::All).await.unwrap();
+        assert_eq!(key.uses, 6);
+        assert!(matches!(
+            storage.acquire_key(Domain::User { id: 2 }).await,
+            Err(PgStorageError::NoMatchingKey(_))
+        ));
+    }
+
+    #[sqlx::test]
+    async fn exhausted_keys(pool: PgPool) {
+        let (storage, key) = setup(pool).await;
+
+        assert!(matches!(
+            storage.acquire_key(Domain::Faction { id: 1 }).await,
+            Err(PgStorageError::NoMatchingKey(_))
+        ));
+
+        storage.reserve_key(key.selector(), 1000).await.unwrap();
+        assert!(matches!(
+            storage.acquire_key(Domain::All).await,
+            Err(PgStorageError::AllKeysExhausted(_))
+        ));
+        assert!(matches!(
+            storage.acquire_key(Domain::Guild { id: 1 }).await,
+            Err(PgStorageError::AllKeysExhausted(_))
+        ));
+        assert!(matches!(
+            storage.acquire_many_keys(Domain::Guild { id: 1 }, 2).await,
+            Err(PgStorageError::AllKeysExhausted(_))
+        ));
+    }
+
     #[sqlx::test]
     async fn test_flag_key_one(pool: PgPool) {
         let (storage, key) = setup(pool).await;
@@ -823,13 +1920,29 @@ pub(crate) mod test {
         assert!(storage.flag_key(key, 2).await.unwrap());
 
         match storage.acquire_key(Domain::All).await.unwrap_err() {
-            PgStorageError::Unavailable(KeySelector::Has(domains)) => {
+            PgStorageError::NoMatchingKey(KeySelector::Has(domains)) => {
                 assert_eq!(domains, vec![Domain::All])
             }
             why => panic!("Expected domain unavailable error but found '{why}'"),
         }
     }
 
+    #[sqlx::test]
+    async fn test_flag_key_fedded(pool: PgPool) {
+        let (storage, key) = setup(pool).await;
+
+        assert!(storage.flag_key(key.clone(), 10).await.unwrap());
+        assert!(storage.acquire_key(Domain::All).await.is_err());
+
+        sqlx::query("update api_keys set cooldown=now() - interval '1 min' where id=$1")
+            .bind(key.id)
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+
+        assert_eq!(storage.acquire_key(Domain::All).await.unwrap().id, key.id);
+    }
+
     #[sqlx::test]
     async fn test_flag_key_many(pool: PgPool) {
         let (storage, key) = setup(pool).await;
@@ -837,7 +1950,7 @@ pub(crate) mod test {
         assert!(storage.flag_key(key, 2).await.unwrap());
 
         match storage.acquire_many_keys(Domain::All, 5).await.unwrap_err() {
-            PgStorageError::Unavailable(KeySelector::Has(domains)) => {
+            PgStorageError::NoMatchingKey(KeySelector::Has(domains)) => {
                 assert_eq!(domains, vec![Domain::All])
             }
             why => panic!("Expected domain unavailable error but found '{why}'"),
@@ -854,10 +1967,122 @@ pub(crate) mod test {
         }
     }
 
-    // HACK: this test is time sensitive and will fail if runs at the top of the minute
+    #[sqlx::test]
+    async fn force_acquire(pool: PgPool) {
+        let (storage, key) = setup(pool).await;
+
+        assert!(storage.flag_key(key.clone(), 2).await.unwrap());
+        assert!(storage.acquire_key(Domain::All).await.is_err());
+
+        let forced = storage.force_acquire_key(key.id).await.unwrap();
+        assert_eq!(forced.id, key.id);
+        assert_eq!(forced.uses, 1);
+
+        assert!(storage.acquire_key(Domain::All).await.is_err());
+        assert!(storage.force_acquire_key(key.id + 1).await.is_err());
+    }
+
+    #[sqlx::test]
+    async fn acquire_many_after_force(pool: PgPool) {
+        let (_, key) = setup(pool.clone()).await;
+        let storage = PgKeyPoolStorage::<Domain>::new(pool, 3);
+        let other = storage
+            .store_key(2, "BBBBBBBBBBBBBBBB".to_owned(), vec![Domain::All])
+            .await
+            .unwrap();
+
+        for _ in 0..5 {
+            storage.force_acquire_key(key.id).await.unwrap();
+        }
+
+        let keys = storage.acquire_many_keys(Domain::All, 10).await.unwrap();
+        assert_eq!(keys.len(), 3);
+        assert!(keys.iter().all(|k| k.id == other.id && k.uses <= 3));
+
+        let uses = |id| {
+            let storage = &storage;
+            async move {
+                storage
+                    .read_key(KeySelector::Id(id))
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .uses
+            }
+        };
+        assert_eq!(uses(key.id).await, 5);
+        assert_eq!(uses(other.id).await, 3);
+    }
+
+    #[sqlx::test]
+    async fn reserve_key(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+
+        let key = storage.reserve_key(Domain::All, 990).await.unwrap();
+        assert_eq!(key.uses, 990);
+        assert!(storage.reserve_key(Domain::All, 20).await.is_err());
+
+        let reservation = crate::KeyReservation::new(storage.clone(), key, 990);
+        for _ in 0..980 {
+            assert!(reservation.take_use().is_some());
+        }
+        assert_eq!(reservation.remaining(), 10);
+        reservation.release().await.unwrap();
+
+        let key = storage.reserve_key(Domain::All, 20).await.unwrap();
+        assert_eq!(key.uses, 1000);
+    }
+
+    #[sqlx::test]
+    async fn reservation_released_on_panic(pool: PgPool) {
+        let (storage, key) = setup(pool).await;
+
+        let reserved = storage.reserve_key(Domain::All, 10).await.unwrap();
+        let reservation = crate::KeyReservation::new(storage.clone(), reserved, 10);
+        let task = tokio::spawn(async move {
+            reservation.take_use().unwrap();
+            panic!("task failed while holding the reservation");
+        });
+        assert!(task.await.unwrap_err().is_panic());
+
+        // the uses are released by a background task
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let key = storage.read_key(key.selector()).await.unwrap().unwrap();
+        assert_eq!(key.uses, 1);
+
+        let reserved = storage.reserve_key(Domain::All, 10).await.unwrap();
+        drop(crate::KeyReservation::new(storage.clone(), reserved, 10).release_on_drop(false));
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let key = storage.read_key(key.selector()).await.unwrap().unwrap();
+        assert_eq!(key.uses, 11);
+    }
+
+    #[sqlx::test]
+    async fn acquire_many_detailed(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+        storage
+            .store_key(1, "ABC".to_owned(), vec![Domain::All])
+            .await
+            .unwrap();
+
+        let keys = storage
+            .acquire_many_keys_detailed(Domain::All, 10)
+            .await
+            .unwrap();
+
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys.iter().map(|(_, count)| count).sum::<u16>(), 10);
+    }
+
+    /// A fixed time in the middle of a minute, so that the uses of the tests which rely on it
+    /// aren't reset halfway through.
+    fn mid_minute() -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::from_timestamp(1_700_000_010, 0).unwrap()
+    }
+
     #[sqlx::test]
     async fn test_concurrent(pool: PgPool) {
-        let storage = Arc::new(setup(pool).await.0);
+        let storage = Arc::new(setup(pool).await.0.with_now_fn(mid_minute));
 
         for _ in 0..10 {
             let mut set = tokio::task::JoinSet::new();
@@ -888,6 +2113,73 @@ pub(crate) mod test {
         }
     }
 
+    #[sqlx::test]
+    async fn skip_locked(pool: PgPool) {
+        setup(pool.clone()).await;
+        let storage = Arc::new(
+            PgKeyPoolStorage::<Domain>::new(pool, 50).with_isolation(Isolation::SkipLocked),
+        );
+
+        let mut set = tokio::task::JoinSet::new();
+        for _ in 0..100 {
+            let storage = storage.clone();
+            set.spawn(async move { storage.acquire_key(Domain::All).await });
+        }
+
+        let mut acquired = 0;
+        while let Some(result) = set.join_next().await {
+            match result.unwrap() {
+                Ok(_) => acquired += 1,
+                Err(PgStorageError::AllKeysExhausted(_)) => (),
+                Err(why) => panic!("{why:?}"),
+            }
+        }
+        assert_eq!(acquired, 50);
+
+        storage.reset_all_usage().await.unwrap();
+        let keys = storage.acquire_many_keys(Domain::All, 10).await.unwrap();
+        assert_eq!(keys.len(), 10);
+    }
+
+    #[sqlx::test]
+    async fn isolation_under_load(pool: PgPool) {
+        setup(pool.clone()).await;
+        for i in 0..9 {
+            PgKeyPoolStorage::<Domain>::new(pool.clone(), 20)
+                .store_key(1, format!("{i:0>16}"), vec![Domain::All])
+                .await
+                .unwrap();
+        }
+
+        // the ten keys can serve exactly 200 requests, in either mode every one of them has to
+        // succeed without any key going over the limit
+        for isolation in [Isolation::RepeatableRead, Isolation::SkipLocked] {
+            let storage = Arc::new(
+                PgKeyPoolStorage::<Domain>::new(pool.clone(), 20)
+                    .with_isolation(isolation)
+                    .with_now_fn(mid_minute),
+            );
+            storage.reset_all_usage().await.unwrap();
+
+            let mut set = tokio::task::JoinSet::new();
+            for _ in 0..200 {
+                let storage = storage.clone();
+                set.spawn(async move { storage.acquire_key(Domain::All).await });
+            }
+            while let Some(result) = set.join_next().await {
+                assert!(result.unwrap().is_ok(), "{isolation:?}");
+            }
+
+            let keys = storage.read_keys(Domain::All).await.unwrap();
+            assert_eq!(keys.len(), 10);
+            assert!(keys.iter().all(|k| k.uses == 20), "{isolation:?}");
+            assert!(matches!(
+                storage.acquire_key(Domain::All).await,
+                Err(PgStorageError::AllKeysExhausted(_))
+            ));
+        }
+    }
+
     #[sqlx::test]
     async fn test_concurrent_spread(pool: PgPool) {
         let storage = Arc::new(setup(pool).await.0);
@@ -928,10 +2220,9 @@ pub(crate) mod test {
         }
     }
 
-    // HACK: this test is time sensitive and will fail if runs at the top of the minute
     #[sqlx::test]
     async fn test_concurrent_many(pool: PgPool) {
-        let storage = Arc::new(setup(pool).await.0);
+        let storage = Arc::new(setup(pool).await.0.with_now_fn(mid_minute));
         for _ in 0..10 {
             let mut set = tokio::task::JoinSet::new();
 
@@ -961,6 +2252,39 @@ pub(crate) mod test {
         }
     }
 
+    #[sqlx::test]
+    async fn pinned_clock(pool: PgPool) {
+        setup(pool.clone()).await;
+        let now = Arc::new(std::sync::Mutex::new(
+            mid_minute() + chrono::Duration::seconds(29),
+        ));
+        let clock = now.clone();
+        let storage =
+            PgKeyPoolStorage::<Domain>::new(pool, 2).with_now_fn(move || *clock.lock().unwrap());
+
+        storage.acquire_key(Domain::All).await.unwrap();
+        storage.acquire_key(Domain::All).await.unwrap();
+        assert!(matches!(
+            storage.acquire_key(Domain::All).await,
+            Err(PgStorageError::AllKeysExhausted(_))
+        ));
+        assert_eq!(
+            storage.next_available_at(Domain::All).await.unwrap(),
+            Some(mid_minute() + chrono::Duration::seconds(30))
+        );
+
+        // the uses are reset once the minute has passed
+        *now.lock().unwrap() += chrono::Duration::seconds(1);
+        let key = storage.acquire_key(Domain::All).await.unwrap();
+        assert_eq!(key.uses, 1);
+
+        // and so is the cooldown after hitting the rate limit
+        storage.flag_key(key, 5).await.unwrap();
+        assert!(storage.acquire_key(Domain::All).await.is_err());
+        *now.lock().unwrap() += chrono::Duration::seconds(60);
+        assert!(storage.acquire_key(Domain::All).await.is_ok());
+    }
+
     #[sqlx::test]
     async fn read_key(pool: PgPool) {
         let (storage, key) = setup(pool).await;
@@ -0,0 +1,154 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use tokio::sync::Semaphore;
+
+use torn_api::{
+    send::{ApiClient, RequestExecutor},
+    ApiRequest, ApiSelection,
+};
+
+/// Token bucket shared between all executors it is handed to.
+///
+/// The bucket holds at most `requests_per_second` tokens and is refilled with one token every
+/// `1 / requests_per_second` seconds by a background task, which exits once the last clone of the
+/// limiter has been dropped.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    permits: Arc<Semaphore>,
+}
+
+impl RateLimiter {
+    /// # Panics
+    ///
+    /// Panics if `requests_per_second` is zero or if called outside of a tokio runtime.
+    pub fn new(requests_per_second: u32) -> Self {
+        assert!(
+            requests_per_second > 0,
+            "requests_per_second must be non-zero"
+        );
+
+        let capacity = requests_per_second as usize;
+        let permits = Arc::new(Semaphore::new(capacity));
+        let weak = Arc::downgrade(&permits);
+
+        tokio::spawn(async move {
+            let period = refill_period(requests_per_second);
+            let mut interval =
+                tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                let Some(permits) = weak.upgrade() else {
+                    break;
+                };
+                if permits.available_permits() < capacity {
+                    permits.add_permits(1);
+                }
+            }
+        });
+
+        Self { permits }
+    }
+
+    pub async fn acquire(&self) {
+        self.permits
+            .acquire()
+            .await
+            .expect("rate limiter semaphore is never closed")
+            .forget();
+    }
+}
+
+/// The time between two refills. Rates above one request per nanosecond are refilled every
+/// nanosecond, since the interval of the refill task can't be zero.
+fn refill_period(requests_per_second: u32) -> Duration {
+    std::cmp::max(
+        Duration::from_secs(1) / requests_per_second,
+        Duration::from_nanos(1),
+    )
+}
+
+/// Wraps another executor and takes a token from a shared [`RateLimiter`] before every request.
+///
+/// Bulk requests are only handed to the inner executor once a token for every id has been
+/// acquired. Retries performed inside the wrapped executor are not gated.
+#[derive(Debug, Clone)]
+pub struct GlobalRateLimitExecutor<E> {
+    inner: E,
+    limiter: RateLimiter,
+}
+
+impl<E> GlobalRateLimitExecutor<E> {
+    pub fn new(inner: E, limiter: RateLimiter) -> Self {
+        Self { inner, limiter }
+    }
+
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+#[async_trait]
+impl<C, E> RequestExecutor<C> for GlobalRateLimitExecutor<E>
+where
+    C: ApiClient,
+    E: RequestExecutor<C> + Send + Sync,
+{
+    type Error = E::Error;
+
+    async fn execute<A>(
+        &self,
+        client: &C,
+        request: ApiRequest<A>,
+        id: Option<String>,
+    ) -> Result<A::Response, Self::Error>
+    where
+        A: ApiSelection,
+    {
+        self.limiter.acquire().await;
+        self.inner.execute(client, request, id).await
+    }
+
+    async fn execute_many<A, I>(
+        &self,
+        client: &C,
+        request: ApiRequest<A>,
+        ids: Vec<I>,
+    ) -> HashMap<I, Result<A::Response, Self::Error>>
+    where
+        A: ApiSelection,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+    {
+        for _ in &ids {
+            self.limiter.acquire().await;
+        }
+        self.inner.execute_many(client, request, ids).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn refill_period_is_never_zero() {
+        assert_eq!(refill_period(2), Duration::from_millis(500));
+        assert_eq!(refill_period(1_000_000_000), Duration::from_nanos(1));
+        assert_eq!(refill_period(u32::MAX), Duration::from_nanos(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limiter_refills() {
+        let limiter = RateLimiter::new(2);
+        let start = tokio::time::Instant::now();
+
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+}
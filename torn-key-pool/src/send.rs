@@ -1,15 +1,16 @@
 use std::{collections::HashMap, sync::Arc};
 
 use async_trait::async_trait;
+use futures::{FutureExt, StreamExt};
 
 use torn_api::{
     send::{ApiClient, ApiProvider, RequestExecutor},
-    ApiRequest, ApiResponse, ApiSelection, ResponseError,
+    ApiRequest, ApiRequestBuilder, ApiResponse, ApiSelection, ResponseError,
 };
 
 use crate::{
-    ApiKey, IntoSelector, KeyAction, KeyDomain, KeyPoolError, KeyPoolExecutor, KeyPoolStorage,
-    KeySelector, PoolOptions,
+    ApiKey, CancellationToken, IntoSelector, KeyAction, KeyDomain, KeyPoolError, KeyPoolExecutor,
+    KeyPoolStorage, KeyReservation, KeyReservationStorage, KeySelector, PoolOptions, SharedError,
 };
 
 #[async_trait]
@@ -29,33 +30,35 @@ where
     where
         A: ApiSelection,
     {
-        if request.comment.is_none() {
-            request.comment = self.options.comment.clone();
-        }
-        if let Some(hook) = self.options.hooks_before.get(&std::any::TypeId::of::<A>()) {
-            let concrete = hook
-                .downcast_ref::<BeforeHook<A, S::Key, S::Domain>>()
-                .unwrap();
-
-            (concrete.body)(&mut request, &self.selector);
-        }
+        self.prepare_request(&mut request);
+        let mut stripped = Vec::new();
+        let mut escalated = false;
+        let mut next_key = None;
         loop {
-            let key = self
-                .storage
-                .acquire_key(self.selector.clone())
-                .await
-                .map_err(KeyPoolError::Storage)?;
+            let key = match next_key.take() {
+                Some(key) => key,
+                None => self
+                    .acquire_key()
+                    .await
+                    .map_err(|e| KeyPoolError::Storage(e.into()))?,
+            };
             let url = request.url(key.value(), id.as_deref());
             let value = client.request(url).await?;
 
             match ApiResponse::from_value(value) {
                 Err(ResponseError::Api { code, reason }) => {
-                    if !self
-                        .storage
-                        .flag_key(key, code)
+                    let retry = self
+                        .handle_api_error(key.clone(), code, &mut stripped)
                         .await
-                        .map_err(KeyPoolError::Storage)?
-                    {
+                        .map_err(|e| KeyPoolError::Storage(e.into()))?;
+                    if code == 16 && !escalated {
+                        escalated = true;
+                        if let Some(key) = self.acquire_escalated(&key).await {
+                            next_key = Some(key);
+                            continue;
+                        }
+                    }
+                    if !retry {
                         return Err(KeyPoolError::Response(ResponseError::Api { code, reason }));
                     }
                 }
@@ -72,14 +75,14 @@ where
                                 self.storage
                                     .remove_key(key.selector())
                                     .await
-                                    .map_err(KeyPoolError::Storage)?;
+                                    .map_err(|e| KeyPoolError::Storage(e.into()))?;
                                 continue;
                             }
                             Err(KeyAction::RemoveDomain(domain)) => {
                                 self.storage
                                     .remove_domain_from_key(key.selector(), domain)
                                     .await
-                                    .map_err(KeyPoolError::Storage)?;
+                                    .map_err(|e| KeyPoolError::Storage(e.into()))?;
                                 continue;
                             }
                             _ => (),
@@ -94,76 +97,492 @@ where
     async fn execute_many<A, I>(
         &self,
         client: &C,
-        mut request: ApiRequest<A>,
+        request: ApiRequest<A>,
         ids: Vec<I>,
     ) -> HashMap<I, Result<A::Response, Self::Error>>
     where
         A: ApiSelection,
         I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
     {
-        let keys = match self
-            .storage
-            .acquire_many_keys(self.selector.clone(), ids.len() as i64)
+        self.execute_many_with_keys(client, request, ids)
+            .await
+            .into_iter()
+            .map(|(id, (_, res))| (id, res))
+            .collect()
+    }
+}
+
+impl<'client, C, S> KeyPoolExecutor<'client, C, S>
+where
+    C: ApiClient,
+    S: KeyPoolStorage + Send + Sync + 'static,
+{
+    async fn acquire_key(&self) -> Result<S::Key, S::Error> {
+        if let Some(key) = self.warm.as_ref().and_then(|w| w.take(1).pop()) {
+            return Ok(key);
+        }
+
+        match &self.selector {
+            KeySelector::Id(id) if self.forced => self.storage.force_acquire_key(id.clone()).await,
+            selector => self.storage.acquire_key(selector.clone()).await,
+        }
+    }
+
+    /// Acquires a key with a higher access level than `key`, which was rejected with code 16.
+    /// Returns `None` if the access level of `key` is unknown, or if there is no such key.
+    async fn acquire_escalated(&self, key: &S::Key) -> Option<S::Key> {
+        if self.forced {
+            return None;
+        }
+        let level = key.access_level()?;
+        self.storage
+            .acquire_key(self.selector.clone().with_access_level(level + 1))
             .await
-        {
-            Ok(keys) => keys,
-            Err(why) => {
-                return ids
-                    .into_iter()
-                    .map(|i| (i, Err(Self::Error::Storage(why.clone()))))
-                    .collect();
+            .ok()
+    }
+
+    async fn acquire_many_keys(&self, number: usize) -> Result<Vec<S::Key>, S::Error> {
+        if self.forced {
+            let mut keys = Vec::with_capacity(number);
+            for _ in 0..number {
+                keys.push(self.acquire_key().await?);
             }
-        };
+            Ok(keys)
+        } else {
+            let mut keys = match &self.warm {
+                Some(warm) => warm.take(number),
+                None => Vec::new(),
+            };
+            if keys.len() < number {
+                let missing = (number - keys.len()) as i64;
+                match self
+                    .storage
+                    .acquire_many_keys(self.selector.clone(), missing)
+                    .await
+                {
+                    Ok(acquired) => keys.extend(acquired),
+                    Err(_) if !keys.is_empty() => (),
+                    Err(why) => return Err(why),
+                }
+            }
+            Ok(keys)
+        }
+    }
 
+    fn apply_options<A>(&self, request: &mut ApiRequest<A>)
+    where
+        A: ApiSelection,
+    {
         if request.comment.is_none() {
             request.comment = self.options.comment.clone();
         }
-        let request_ref = &request;
-
-        let tuples =
-            futures::future::join_all(std::iter::zip(ids, keys).map(|(id, mut key)| async move {
-                let id_string = id.to_string();
-                loop {
-                    let url = request_ref.url(key.value(), Some(&id_string));
-                    let value = match client.request(url).await {
-                        Ok(v) => v,
-                        Err(why) => return (id, Err(Self::Error::Client(why))),
-                    };
-
-                    match ApiResponse::from_value(value) {
-                        Err(ResponseError::Api { code, reason }) => {
-                            match self.storage.flag_key(key, code).await {
-                                Ok(false) => {
-                                    return (
-                                        id,
-                                        Err(KeyPoolError::Response(ResponseError::Api {
-                                            code,
-                                            reason,
-                                        })),
-                                    )
-                                }
-                                Ok(true) => (),
-                                Err(why) => return (id, Err(KeyPoolError::Storage(why))),
+        for (name, value) in &self.options.query_items {
+            if !request.query_items.iter().any(|(n, _)| n == name) {
+                request.query_items.push((name, value.clone()));
+            }
+        }
+    }
+
+    fn prepare_request<A>(&self, request: &mut ApiRequest<A>)
+    where
+        A: ApiSelection,
+    {
+        self.apply_options(request);
+        if let Some(hook) = self.options.hooks_before.get(&std::any::TypeId::of::<A>()) {
+            let concrete = hook
+                .downcast_ref::<BeforeHook<A, S::Key, S::Domain>>()
+                .unwrap();
+
+            (concrete.body)(request, &self.selector);
+        }
+    }
+
+    /// Flags the key after the API returned an error. Returns whether the request should be
+    /// retried with a different key.
+    ///
+    /// Keys with an insufficient access level (code 16) aren't flagged; instead the domain the
+    /// request was made for is removed from them, so that they keep serving other requests.
+    async fn handle_api_error(
+        &self,
+        key: S::Key,
+        code: u8,
+        stripped: &mut Vec<<S::Key as ApiKey>::IdType>,
+    ) -> Result<bool, S::Error> {
+        if code == 16 {
+            return match self.selector.single_domain() {
+                // the key might have been selected through a fallback domain, in which case
+                // removing the domain doesn't take it out of the rotation
+                Some(domain) if !stripped.contains(&key.id()) => {
+                    stripped.push(key.id());
+                    self.storage
+                        .remove_domain_from_key(key.selector(), domain.clone())
+                        .await?;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            };
+        }
+
+        // a forced key would just be used again, so retrying is pointless
+        Ok(self.storage.flag_key(key, code).await? && !self.forced)
+    }
+
+    /// Builds the url that [`RequestExecutor::execute`] would request, without sending it.
+    ///
+    /// The key is only read from the storage, so its uses are not incremented. Returns `None` if
+    /// no key matches the selector.
+    pub async fn dry_run<A>(
+        &self,
+        mut request: ApiRequest<A>,
+        id: Option<String>,
+    ) -> Result<Option<(<S::Key as ApiKey>::IdType, String)>, S::Error>
+    where
+        A: ApiSelection,
+    {
+        self.prepare_request(&mut request);
+
+        let key = self.storage.read_key(self.selector.clone()).await?;
+
+        Ok(key.map(|k| (k.id(), request.url(k.value(), id.as_deref()))))
+    }
+
+    /// Same as [`RequestExecutor::execute_many`], but additionally returns the id of the key
+    /// which produced each result. The id is `None` if the request failed before a key could be
+    /// acquired.
+    #[allow(clippy::type_complexity)]
+    pub async fn execute_many_with_keys<A, I>(
+        &self,
+        client: &C,
+        request: ApiRequest<A>,
+        ids: Vec<I>,
+    ) -> HashMap<
+        I,
+        (
+            Option<<S::Key as ApiKey>::IdType>,
+            Result<A::Response, KeyPoolError<S::Error, C::Error>>,
+        ),
+    >
+    where
+        A: ApiSelection,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+    {
+        self.execute_many_inner(client, request, ids, &|_, _| (), None, false)
+            .await
+    }
+
+    /// Same as [`Self::execute_many_with_keys`], but stops dispatching requests once `cancel` has
+    /// been cancelled. Requests which are in flight at that point are abandoned. All unfinished
+    /// requests resolve to [`KeyPoolError::Cancelled`].
+    #[allow(clippy::type_complexity)]
+    pub async fn execute_many_cancellable<A, I>(
+        &self,
+        client: &C,
+        request: ApiRequest<A>,
+        ids: Vec<I>,
+        cancel: &CancellationToken,
+    ) -> HashMap<
+        I,
+        (
+            Option<<S::Key as ApiKey>::IdType>,
+            Result<A::Response, KeyPoolError<S::Error, C::Error>>,
+        ),
+    >
+    where
+        A: ApiSelection,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+    {
+        self.execute_many_inner(client, request, ids, &|_, _| (), Some(cancel), false)
+            .await
+    }
+
+    /// Same as [`Self::execute_many_with_keys`], but calls `progress` with the number of
+    /// finished requests and the total number of requests every time a request finishes.
+    #[allow(clippy::type_complexity)]
+    pub async fn execute_many_with_progress<A, I>(
+        &self,
+        client: &C,
+        request: ApiRequest<A>,
+        ids: Vec<I>,
+        progress: &(dyn Fn(usize, usize) + Send + Sync),
+    ) -> HashMap<
+        I,
+        (
+            Option<<S::Key as ApiKey>::IdType>,
+            Result<A::Response, KeyPoolError<S::Error, C::Error>>,
+        ),
+    >
+    where
+        A: ApiSelection,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+    {
+        self.execute_many_inner(client, request, ids, progress, None, false)
+            .await
+    }
+
+    /// Same as [`RequestExecutor::execute_many`], but stops at the first failed request and
+    /// returns its error. Requests which are in flight at that point are abandoned and the
+    /// remaining requests aren't sent.
+    #[allow(clippy::type_complexity)]
+    pub async fn try_execute_many<A, I>(
+        &self,
+        client: &C,
+        request: ApiRequest<A>,
+        ids: Vec<I>,
+    ) -> Result<Vec<(I, A::Response)>, KeyPoolError<S::Error, C::Error>>
+    where
+        A: ApiSelection,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+    {
+        self.execute_many_inner(client, request, ids, &|_, _| (), None, true)
+            .await
+            .into_iter()
+            .map(|(id, (_, res))| res.map(|r| (id, r)))
+            .collect()
+    }
+
+    #[allow(clippy::type_complexity)]
+    async fn execute_many_inner<A, I>(
+        &self,
+        client: &C,
+        mut request: ApiRequest<A>,
+        ids: Vec<I>,
+        progress: &(dyn Fn(usize, usize) + Send + Sync),
+        cancel: Option<&CancellationToken>,
+        fail_fast: bool,
+    ) -> HashMap<
+        I,
+        (
+            Option<<S::Key as ApiKey>::IdType>,
+            Result<A::Response, KeyPoolError<S::Error, C::Error>>,
+        ),
+    >
+    where
+        A: ApiSelection,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+    {
+        let total = ids.len();
+        let done = &std::sync::atomic::AtomicUsize::new(0);
+        let state = &BulkState {
+            waiting: std::sync::atomic::AtomicUsize::new(0),
+            retries: self
+                .options
+                .bulk_retry_budget
+                .map(std::sync::atomic::AtomicUsize::new),
+            cancel,
+        };
+
+        if cancel.is_some_and(CancellationToken::is_cancelled) {
+            progress(total, total);
+            return ids
+                .into_iter()
+                .map(|i| (i, (None, Err(KeyPoolError::Cancelled))))
+                .collect();
+        }
+
+        let concurrency = self.options.bulk_concurrency();
+        self.apply_options(&mut request);
+
+        let mut ids = ids.into_iter();
+        let mut keys = Vec::new().into_iter();
+        let mut pending = futures::stream::FuturesUnordered::new();
+        let mut result = HashMap::with_capacity(total);
+
+        // keys are only acquired once there is room for more requests to be sent, so that large
+        // batches don't reserve the uses of the whole pool up front
+        loop {
+            while pending.len() < concurrency {
+                let Some(id) = ids.next() else {
+                    break;
+                };
+                let key = match keys.next() {
+                    Some(key) => Ok(Some(key)),
+                    None if cancel.is_some_and(CancellationToken::is_cancelled) => Ok(None),
+                    None => {
+                        let number = std::cmp::min(concurrency - pending.len(), ids.len() + 1);
+                        match self.acquire_many_keys(number).await {
+                            // the storage hands out fewer keys than requested if the pool
+                            // doesn't have enough uses left, the remaining requests try to
+                            // acquire a key for themselves
+                            Ok(acquired) => {
+                                keys = acquired.into_iter();
+                                Ok(keys.next())
                             }
+                            Err(why) => Err(SharedError::from(why)),
                         }
-                        Err(parsing_error) => {
-                            return (id, Err(KeyPoolError::Response(parsing_error)))
+                    }
+                };
+
+                pending.push(
+                    self.execute_single(client, &request, id, key, state)
+                        .inspect(|_| {
+                            let done = done.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+                            progress(done, total);
+                        }),
+                );
+            }
+
+            if let Some(hook) = &self.options.backlog_hook {
+                (hook.0)(crate::BulkBacklog {
+                    in_flight: pending.len(),
+                    waiting_for_key: state.waiting.load(std::sync::atomic::Ordering::Relaxed),
+                    queued: ids.len(),
+                });
+            }
+
+            match pending.next().await {
+                // dropping the pending futures abandons the requests which are still in flight
+                Some((id, res)) if fail_fast && res.1.is_err() => {
+                    result.insert(id, res);
+                    break;
+                }
+                Some((id, res)) => {
+                    result.insert(id, res);
+                }
+                None => break,
+            }
+        }
+
+        result
+    }
+
+    #[allow(clippy::type_complexity)]
+    async fn execute_single<A, I>(
+        &self,
+        client: &C,
+        request: &ApiRequest<A>,
+        id: I,
+        key: Result<Option<S::Key>, SharedError<S::Error>>,
+        state: &BulkState<'_>,
+    ) -> (
+        I,
+        (
+            Option<<S::Key as ApiKey>::IdType>,
+            Result<A::Response, KeyPoolError<S::Error, C::Error>>,
+        ),
+    )
+    where
+        A: ApiSelection,
+        I: ToString,
+    {
+        let BulkState {
+            waiting,
+            retries,
+            cancel,
+        } = state;
+        let cancel = *cancel;
+        let mut key = match key {
+            Ok(Some(key)) => key,
+            Ok(None) if cancel.is_some_and(CancellationToken::is_cancelled) => {
+                return (id, (None, Err(KeyPoolError::Cancelled)))
+            }
+            Ok(None) => {
+                waiting.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                let key = self.acquire_key().await;
+                waiting.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                match key {
+                    Ok(key) => key,
+                    Err(why) => return (id, (None, Err(KeyPoolError::Storage(why.into())))),
+                }
+            }
+            Err(why) => return (id, (None, Err(KeyPoolError::Storage(why)))),
+        };
+        let id_string = id.to_string();
+        let mut stripped = Vec::new();
+        let mut escalated = false;
+        loop {
+            let key_id = key.id();
+            if cancel.is_some_and(CancellationToken::is_cancelled) {
+                return (id, (Some(key_id), Err(KeyPoolError::Cancelled)));
+            }
+
+            let url = request.url(key.value(), Some(&id_string));
+            let response = match cancel {
+                Some(cancel) => {
+                    let request = std::pin::pin!(client.request(url));
+                    let cancelled = std::pin::pin!(cancel.cancelled());
+                    match futures::future::select(request, cancelled).await {
+                        futures::future::Either::Left((response, _)) => response,
+                        futures::future::Either::Right(_) => {
+                            return (id, (Some(key_id), Err(KeyPoolError::Cancelled)))
                         }
-                        Ok(res) => return (id, Ok(res.into())),
-                    };
+                    }
+                }
+                None => client.request(url).await,
+            };
+            let value = match response {
+                Ok(v) => v,
+                Err(why) => return (id, (Some(key_id), Err(KeyPoolError::Client(why)))),
+            };
 
-                    key = match self.storage.acquire_key(self.selector.clone()).await {
-                        Ok(k) => k,
-                        Err(why) => return (id, Err(Self::Error::Storage(why))),
-                    };
+            match ApiResponse::from_value(value) {
+                Err(ResponseError::Api { code, reason }) => {
+                    let retry = self
+                        .handle_api_error(key.clone(), code, &mut stripped)
+                        .await;
+                    if code == 16 && !escalated && retry.is_ok() {
+                        escalated = true;
+                        if let Some(escalated) = self.acquire_escalated(&key).await {
+                            key = escalated;
+                            continue;
+                        }
+                    }
+                    // the retries of all requests share one budget, so that they can't add up
+                    // to more traffic than the pool can handle
+                    let retry = retry.map(|retry| match retries {
+                        Some(retries) if retry => retries
+                            .fetch_update(
+                                std::sync::atomic::Ordering::Relaxed,
+                                std::sync::atomic::Ordering::Relaxed,
+                                |n| n.checked_sub(1),
+                            )
+                            .is_ok(),
+                        _ => retry,
+                    });
+                    match retry {
+                        Ok(false) => {
+                            return (
+                                id,
+                                (
+                                    Some(key_id),
+                                    Err(KeyPoolError::Response(ResponseError::Api {
+                                        code,
+                                        reason,
+                                    })),
+                                ),
+                            )
+                        }
+                        Ok(true) => (),
+                        Err(why) => {
+                            return (id, (Some(key_id), Err(KeyPoolError::Storage(why.into()))))
+                        }
+                    }
                 }
-            }))
-            .await;
+                Err(parsing_error) => {
+                    return (
+                        id,
+                        (Some(key_id), Err(KeyPoolError::Response(parsing_error))),
+                    )
+                }
+                Ok(res) => return (id, (Some(key_id), Ok(res.into()))),
+            };
 
-        HashMap::from_iter(tuples)
+            key = match self.acquire_key().await {
+                Ok(k) => k,
+                Err(why) => return (id, (None, Err(KeyPoolError::Storage(why.into())))),
+            };
+        }
     }
 }
 
+/// State which is shared by all requests of a bulk request.
+struct BulkState<'a> {
+    /// Requests which are waiting to acquire a key of their own.
+    waiting: std::sync::atomic::AtomicUsize,
+    /// The remaining retry budget, if any.
+    retries: Option<std::sync::atomic::AtomicUsize>,
+    cancel: Option<&'a CancellationToken>,
+}
+
 #[allow(clippy::type_complexity)]
 pub struct BeforeHook<A, K, D>
 where
@@ -217,6 +636,38 @@ where
         self
     }
 
+    /// The maximum number of requests which are in flight at once during bulk requests.
+    /// Defaults to 25.
+    pub fn bulk_concurrency(mut self, concurrency: usize) -> Self {
+        self.options.bulk_concurrency = Some(concurrency.max(1));
+        self
+    }
+
+    /// The maximum number of times requests of a single bulk request are retried with another
+    /// key after an API error, summed over all requests. Once the budget is used up, failed
+    /// requests return their error instead. Unlimited by default.
+    pub fn bulk_retry_budget(mut self, retries: usize) -> Self {
+        self.options.bulk_retry_budget = Some(retries);
+        self
+    }
+
+    /// Called with the current backlog whenever a bulk request starts or finishes a request.
+    /// Useful to choose a [`bulk_concurrency`](Self::bulk_concurrency) which fits the pool size.
+    pub fn on_bulk_backlog(
+        mut self,
+        hook: impl Fn(crate::BulkBacklog) + Send + Sync + 'static,
+    ) -> Self {
+        self.options.backlog_hook = Some(crate::BacklogHook(Box::new(hook)));
+        self
+    }
+
+    /// Adds a query parameter to every request made through the pool, unless the request sets
+    /// the parameter itself.
+    pub fn query_item(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.options.query_items.push((name, value.to_string()));
+        self
+    }
+
     pub fn hook_before<A>(
         mut self,
         hook: impl Fn(&mut ApiRequest<A>, &KeySelector<S::Key, S::Domain>) + Send + Sync + 'static,
@@ -277,7 +728,19 @@ where
     C: ApiClient,
     S: KeyPoolStorage + Send + Sync + 'static,
 {
-    pub fn torn_api<I>(&self, selector: I) -> ApiProvider<C, KeyPoolExecutor<C, S>>
+    /// Sends all requests through the key with the given id, bypassing cooldowns and rate
+    /// limits. See [`KeyPoolExecutor::forced`].
+    pub fn torn_api_forced(
+        &self,
+        key_id: <S::Key as ApiKey>::IdType,
+    ) -> ApiProvider<'_, C, KeyPoolExecutor<'_, C, S>> {
+        ApiProvider::new(
+            &self.client,
+            KeyPoolExecutor::forced(&self.storage, key_id, self.options.clone()),
+        )
+    }
+
+    pub fn torn_api<I>(&self, selector: I) -> ApiProvider<'_, C, KeyPoolExecutor<'_, C, S>>
     where
         I: IntoSelector<S::Key, S::Domain>,
     {
@@ -290,6 +753,269 @@ where
             ),
         )
     }
+
+    /// Acquires `number` uses of keys matching the selector ahead of time and returns a provider
+    /// which spends them before acquiring keys from the storage, so that the first requests of a
+    /// burst don't have to wait for the storage. Unspent uses expire at the end of the current
+    /// minute.
+    pub async fn warm<Sel>(
+        &self,
+        selector: Sel,
+        number: usize,
+    ) -> Result<ApiProvider<'_, C, KeyPoolExecutor<'_, C, S>>, S::Error>
+    where
+        Sel: IntoSelector<S::Key, S::Domain>,
+    {
+        let selector = selector.into_selector();
+        let keys = self
+            .storage
+            .acquire_many_keys(selector.clone(), number as i64)
+            .await?;
+
+        let mut executor = KeyPoolExecutor::new(&self.storage, selector, self.options.clone());
+        executor.warm = Some(Arc::new(crate::WarmKeys::new(keys)));
+
+        Ok(ApiProvider::new(&self.client, executor))
+    }
+
+    /// Executes the same request for all `ids` and returns the id of the key that served each
+    /// response alongside the result.
+    #[allow(clippy::type_complexity)]
+    pub async fn execute_many_with_keys<A, F, L, I, Sel>(
+        &self,
+        selector: Sel,
+        ids: L,
+        build: F,
+    ) -> HashMap<
+        I,
+        (
+            Option<<S::Key as ApiKey>::IdType>,
+            Result<A::Response, KeyPoolError<S::Error, C::Error>>,
+        ),
+    >
+    where
+        A: ApiSelection,
+        F: FnOnce(ApiRequestBuilder<A>) -> ApiRequestBuilder<A>,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+        L: IntoIterator<Item = I>,
+        Sel: IntoSelector<S::Key, S::Domain>,
+    {
+        let builder = build(ApiRequestBuilder::default());
+
+        KeyPoolExecutor::new(
+            &self.storage,
+            selector.into_selector(),
+            self.options.clone(),
+        )
+        .execute_many_with_keys(&self.client, builder.request, Vec::from_iter(ids))
+        .await
+    }
+
+    /// Executes the same request for all `ids`, calling `progress` with the number of finished
+    /// requests and the total number of requests whenever one of them finishes.
+    #[allow(clippy::type_complexity)]
+    pub async fn execute_many_with_progress<A, F, L, I, Sel, P>(
+        &self,
+        selector: Sel,
+        ids: L,
+        build: F,
+        progress: P,
+    ) -> HashMap<I, Result<A::Response, KeyPoolError<S::Error, C::Error>>>
+    where
+        A: ApiSelection,
+        F: FnOnce(ApiRequestBuilder<A>) -> ApiRequestBuilder<A>,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+        L: IntoIterator<Item = I>,
+        Sel: IntoSelector<S::Key, S::Domain>,
+        P: Fn(usize, usize) + Send + Sync,
+    {
+        let builder = build(ApiRequestBuilder::default());
+
+        KeyPoolExecutor::new(
+            &self.storage,
+            selector.into_selector(),
+            self.options.clone(),
+        )
+        .execute_many_with_progress(
+            &self.client,
+            builder.request,
+            Vec::from_iter(ids),
+            &progress,
+        )
+        .await
+        .into_iter()
+        .map(|(id, (_, res))| (id, res))
+        .collect()
+    }
+
+    /// Executes the same request for all `ids` until `cancel` is cancelled. See
+    /// [`KeyPoolExecutor::execute_many_cancellable`].
+    #[allow(clippy::type_complexity)]
+    pub async fn execute_many_cancellable<A, F, L, I, Sel>(
+        &self,
+        selector: Sel,
+        ids: L,
+        build: F,
+        cancel: &CancellationToken,
+    ) -> HashMap<I, Result<A::Response, KeyPoolError<S::Error, C::Error>>>
+    where
+        A: ApiSelection,
+        F: FnOnce(ApiRequestBuilder<A>) -> ApiRequestBuilder<A>,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+        L: IntoIterator<Item = I>,
+        Sel: IntoSelector<S::Key, S::Domain>,
+    {
+        let builder = build(ApiRequestBuilder::default());
+
+        KeyPoolExecutor::new(
+            &self.storage,
+            selector.into_selector(),
+            self.options.clone(),
+        )
+        .execute_many_cancellable(&self.client, builder.request, Vec::from_iter(ids), cancel)
+        .await
+        .into_iter()
+        .map(|(id, (_, res))| (id, res))
+        .collect()
+    }
+
+    /// Executes the same request for all `ids`, aborting the whole batch on the first error.
+    /// See [`KeyPoolExecutor::try_execute_many`].
+    #[allow(clippy::type_complexity)]
+    pub async fn try_execute_many<A, F, L, I, Sel>(
+        &self,
+        selector: Sel,
+        ids: L,
+        build: F,
+    ) -> Result<Vec<(I, A::Response)>, KeyPoolError<S::Error, C::Error>>
+    where
+        A: ApiSelection,
+        F: FnOnce(ApiRequestBuilder<A>) -> ApiRequestBuilder<A>,
+        I: ToString + std::hash::Hash + std::cmp::Eq + Send + Sync,
+        L: IntoIterator<Item = I>,
+        Sel: IntoSelector<S::Key, S::Domain>,
+    {
+        let builder = build(ApiRequestBuilder::default());
+
+        KeyPoolExecutor::new(
+            &self.storage,
+            selector.into_selector(),
+            self.options.clone(),
+        )
+        .try_execute_many(&self.client, builder.request, Vec::from_iter(ids))
+        .await
+    }
+
+    async fn execute_with<A, F>(
+        &self,
+        selector: KeySelector<S::Key, S::Domain>,
+        build: F,
+    ) -> Result<A::Response, KeyPoolError<S::Error, C::Error>>
+    where
+        A: ApiSelection,
+        F: FnOnce(ApiRequestBuilder<A>) -> ApiRequestBuilder<A>,
+    {
+        let builder = build(ApiRequestBuilder::default());
+
+        KeyPoolExecutor::new(&self.storage, selector, self.options.clone())
+            .execute(&self.client, builder.request, builder.id)
+            .await
+    }
+
+    /// Concurrently executes two requests of different categories, using keys from the same
+    /// selector. Fails with the first error encountered.
+    #[allow(clippy::type_complexity)]
+    pub async fn try_join2<A1, A2, F1, F2, Sel>(
+        &self,
+        selector: Sel,
+        build1: F1,
+        build2: F2,
+    ) -> Result<(A1::Response, A2::Response), KeyPoolError<S::Error, C::Error>>
+    where
+        A1: ApiSelection,
+        A2: ApiSelection,
+        F1: FnOnce(ApiRequestBuilder<A1>) -> ApiRequestBuilder<A1>,
+        F2: FnOnce(ApiRequestBuilder<A2>) -> ApiRequestBuilder<A2>,
+        Sel: IntoSelector<S::Key, S::Domain>,
+    {
+        let selector = selector.into_selector();
+
+        futures::try_join!(
+            self.execute_with(selector.clone(), build1),
+            self.execute_with(selector, build2)
+        )
+    }
+
+    /// Same as [`Self::try_join2`], but for three requests.
+    #[allow(clippy::type_complexity)]
+    pub async fn try_join3<A1, A2, A3, F1, F2, F3, Sel>(
+        &self,
+        selector: Sel,
+        build1: F1,
+        build2: F2,
+        build3: F3,
+    ) -> Result<(A1::Response, A2::Response, A3::Response), KeyPoolError<S::Error, C::Error>>
+    where
+        A1: ApiSelection,
+        A2: ApiSelection,
+        A3: ApiSelection,
+        F1: FnOnce(ApiRequestBuilder<A1>) -> ApiRequestBuilder<A1>,
+        F2: FnOnce(ApiRequestBuilder<A2>) -> ApiRequestBuilder<A2>,
+        F3: FnOnce(ApiRequestBuilder<A3>) -> ApiRequestBuilder<A3>,
+        Sel: IntoSelector<S::Key, S::Domain>,
+    {
+        let selector = selector.into_selector();
+
+        futures::try_join!(
+            self.execute_with(selector.clone(), build1),
+            self.execute_with(selector.clone(), build2),
+            self.execute_with(selector, build3)
+        )
+    }
+
+    /// Returns the id of the key and the url that would be used for this request, without
+    /// performing it. See [`KeyPoolExecutor::dry_run`].
+    #[allow(clippy::type_complexity)]
+    pub async fn dry_run<A, F, Sel>(
+        &self,
+        selector: Sel,
+        build: F,
+    ) -> Result<Option<(<S::Key as ApiKey>::IdType, String)>, S::Error>
+    where
+        A: ApiSelection,
+        F: FnOnce(ApiRequestBuilder<A>) -> ApiRequestBuilder<A>,
+        Sel: IntoSelector<S::Key, S::Domain>,
+    {
+        let builder = build(ApiRequestBuilder::default());
+
+        KeyPoolExecutor::<C, S>::new(
+            &self.storage,
+            selector.into_selector(),
+            self.options.clone(),
+        )
+        .dry_run(builder.request, builder.id)
+        .await
+    }
+}
+
+impl<C, S> KeyPool<C, S>
+where
+    C: ApiClient,
+    S: KeyReservationStorage + Clone + Send + Sync + 'static,
+{
+    /// Reserves `uses` uses of a single key, for operations which have to be performed with
+    /// the same key. See [`KeyReservation`].
+    pub async fn reserve_key<Sel>(
+        &self,
+        selector: Sel,
+        uses: i16,
+    ) -> Result<KeyReservation<S>, S::Error>
+    where
+        Sel: IntoSelector<S::Key, S::Domain>,
+    {
+        let key = self.storage.reserve_key(selector, uses).await?;
+        Ok(KeyReservation::new(self.storage.clone(), key, uses))
+    }
 }
 
 pub trait WithStorage {
@@ -297,7 +1023,7 @@ pub trait WithStorage {
         &'a self,
         storage: &'a S,
         selector: I,
-    ) -> ApiProvider<Self, KeyPoolExecutor<Self, S>>
+    ) -> ApiProvider<'a, Self, KeyPoolExecutor<'a, Self, S>>
     where
         Self: ApiClient + Sized,
         S: KeyPoolStorage + Send + Sync + 'static,
@@ -334,6 +1060,17 @@ mod test {
         _ = response.profile().unwrap();
     }
 
+    #[sqlx::test]
+    async fn pool_futures_are_send(pool: PgPool) {
+        fn assert_send<T: Send>(_: &T) {}
+
+        let (storage, _) = setup(pool).await;
+        let pool = PoolBuilder::new(reqwest::Client::default(), storage).build();
+
+        assert_send(&pool.torn_api(Domain::All).user(|b| b));
+        assert_send(&pool.torn_api(Domain::All).users([1, 2], |b| b));
+    }
+
     #[sqlx::test]
     async fn test_with_storage_request(pool: PgPool) {
         let (storage, _) = setup(pool).await;
@@ -346,6 +1083,493 @@ mod test {
         _ = response.profile().unwrap();
     }
 
+    #[sqlx::test]
+    async fn cloned_pool(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+        let pool = PoolBuilder::new(reqwest::Client::default(), storage)
+            .comment("api.rs")
+            .build();
+
+        let cloned = pool.clone();
+        assert!(Arc::ptr_eq(&pool.options, &cloned.options));
+
+        let handle = tokio::spawn(async move {
+            let response = cloned.torn_api(Domain::All).user(|b| b).await.unwrap();
+            _ = response.profile().unwrap();
+        });
+
+        let response = pool.torn_api(Domain::All).user(|b| b).await.unwrap();
+        _ = response.profile().unwrap();
+        handle.await.unwrap();
+    }
+
+    #[sqlx::test]
+    async fn bulk_progress(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+        let pool = PoolBuilder::new(reqwest::Client::default(), storage).build();
+
+        let reports = std::sync::Mutex::new(Vec::new());
+        let response = pool
+            .execute_many_with_progress(
+                Domain::All,
+                [1, 2111649],
+                |b: ApiRequestBuilder<torn_api::user::Selection>| {
+                    b.selections([torn_api::user::Selection::Basic])
+                },
+                |done, total| reports.lock().unwrap().push((done, total)),
+            )
+            .await;
+
+        assert_eq!(response.len(), 2);
+        assert_eq!(reports.into_inner().unwrap(), vec![(1, 2), (2, 2)]);
+    }
+
+    #[sqlx::test]
+    async fn bulk_cancelled(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+        let pool = PoolBuilder::new(reqwest::Client::default(), storage).build();
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        let response = pool
+            .execute_many_cancellable(
+                Domain::All,
+                [1, 2111649],
+                |b: ApiRequestBuilder<torn_api::user::Selection>| {
+                    b.selections([torn_api::user::Selection::Basic])
+                },
+                &cancel,
+            )
+            .await;
+
+        assert_eq!(response.len(), 2);
+        for res in response.into_values() {
+            assert!(matches!(res, Err(KeyPoolError::Cancelled)));
+        }
+    }
+
+    #[sqlx::test]
+    async fn join_requests(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+        let pool = PoolBuilder::new(reqwest::Client::default(), storage).build();
+
+        let (user, faction) = pool
+            .try_join2(
+                Domain::All,
+                |b: ApiRequestBuilder<torn_api::user::Selection>| {
+                    b.selections([torn_api::user::Selection::Basic])
+                },
+                |b: ApiRequestBuilder<torn_api::faction::Selection>| {
+                    b.selections([torn_api::faction::Selection::Basic])
+                },
+            )
+            .await
+            .unwrap();
+
+        user.basic().unwrap();
+        faction.basic().unwrap();
+    }
+
+    #[sqlx::test]
+    async fn bulk_with_keys(pool: PgPool) {
+        let (storage, key) = setup(pool).await;
+        let pool = PoolBuilder::new(reqwest::Client::default(), storage).build();
+
+        let response = pool
+            .execute_many_with_keys(
+                Domain::All,
+                [1, 2111649],
+                |b: ApiRequestBuilder<torn_api::user::Selection>| {
+                    b.selections([torn_api::user::Selection::Basic])
+                },
+            )
+            .await;
+
+        assert_eq!(response.len(), 2);
+        for (key_id, res) in response.into_values() {
+            assert_eq!(key_id, Some(key.id));
+            res.unwrap().basic().unwrap();
+        }
+    }
+
+    struct StaticClient;
+
+    #[async_trait]
+    impl ApiClient for StaticClient {
+        type Error = std::convert::Infallible;
+
+        async fn request(&self, _url: String) -> Result<serde_json::Value, Self::Error> {
+            Ok(serde_json::json!({ "player_id": 1 }))
+        }
+    }
+
+    #[sqlx::test]
+    async fn bulk_fewer_keys(pool: PgPool) {
+        setup(pool.clone()).await;
+        let storage = crate::postgres::PgKeyPoolStorage::new(pool, 3);
+        storage
+            .store_key(2, "BBBBBBBBBBBBBBBB".to_owned(), vec![Domain::All])
+            .await
+            .unwrap();
+        let pool = PoolBuilder::new(StaticClient, storage).build();
+
+        let response = pool
+            .execute_many_with_keys(
+                Domain::All,
+                0..10,
+                |b: ApiRequestBuilder<torn_api::user::Selection>| b,
+            )
+            .await;
+
+        // the two keys only have 6 uses between them, but no request is dropped
+        assert_eq!(response.len(), 10);
+        let served = response.values().filter(|(_, res)| res.is_ok()).count();
+        assert_eq!(served, 6);
+        for (key_id, res) in response.into_values() {
+            assert_eq!(key_id.is_some(), res.is_ok());
+        }
+    }
+
+    #[derive(Default)]
+    struct CountingClient {
+        in_flight: std::sync::atomic::AtomicUsize,
+        max_in_flight: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ApiClient for CountingClient {
+        type Error = std::convert::Infallible;
+
+        async fn request(&self, _url: String) -> Result<serde_json::Value, Self::Error> {
+            use std::sync::atomic::Ordering;
+
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            Ok(serde_json::json!({ "player_id": 1 }))
+        }
+    }
+
+    #[sqlx::test]
+    async fn try_execute_many(pool: PgPool) {
+        struct FailingClient(std::sync::atomic::AtomicUsize);
+
+        #[async_trait]
+        impl ApiClient for FailingClient {
+            type Error = std::convert::Infallible;
+
+            async fn request(&self, url: String) -> Result<serde_json::Value, Self::Error> {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                if url.contains("/user/3?") {
+                    Ok(serde_json::json!({ "error": { "code": 6, "error": "Incorrect ID" } }))
+                } else {
+                    Ok(serde_json::json!({ "player_id": 1 }))
+                }
+            }
+        }
+
+        let (storage, _) = setup(pool).await;
+        let pool = PoolBuilder::new(FailingClient(Default::default()), storage)
+            .bulk_concurrency(2)
+            .build();
+
+        let response = pool
+            .try_execute_many(
+                Domain::All,
+                0..3,
+                |b: ApiRequestBuilder<torn_api::user::Selection>| b,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.len(), 3);
+
+        let response = pool
+            .try_execute_many(
+                Domain::All,
+                0..100,
+                |b: ApiRequestBuilder<torn_api::user::Selection>| b,
+            )
+            .await;
+        assert!(matches!(
+            response,
+            Err(KeyPoolError::Response(ResponseError::Api { code: 6, .. }))
+        ));
+        assert!(pool.client.0.load(std::sync::atomic::Ordering::SeqCst) < 3 + 10);
+    }
+
+    #[sqlx::test]
+    async fn bulk_retry_budget(pool: PgPool) {
+        struct RateLimitedClient(std::sync::atomic::AtomicUsize);
+
+        #[async_trait]
+        impl ApiClient for RateLimitedClient {
+            type Error = std::convert::Infallible;
+
+            async fn request(&self, _url: String) -> Result<serde_json::Value, Self::Error> {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(serde_json::json!({ "error": { "code": 5, "error": "Too many requests" } }))
+            }
+        }
+
+        let (storage, _) = setup(pool).await;
+        for i in 0..10 {
+            storage
+                .store_key(i, format!("{i:0>16}"), vec![Domain::All])
+                .await
+                .unwrap();
+        }
+        let pool = PoolBuilder::new(RateLimitedClient(Default::default()), storage)
+            .bulk_concurrency(1)
+            .bulk_retry_budget(3)
+            .build();
+
+        let response = pool
+            .execute_many_with_keys(
+                Domain::All,
+                0..5,
+                |b: ApiRequestBuilder<torn_api::user::Selection>| b,
+            )
+            .await;
+
+        assert!(response.values().all(|(_, res)| matches!(
+            res,
+            Err(KeyPoolError::Response(ResponseError::Api { code: 5, .. }))
+        )));
+        assert_eq!(
+            pool.client.0.load(std::sync::atomic::Ordering::SeqCst),
+            5 + 3
+        );
+    }
+
+    #[sqlx::test]
+    async fn access_level_escalation(pool: PgPool) {
+        struct AccessClient;
+
+        #[async_trait]
+        impl ApiClient for AccessClient {
+            type Error = std::convert::Infallible;
+
+            async fn request(&self, url: String) -> Result<serde_json::Value, Self::Error> {
+                if url.contains("key=BBBBBBBBBBBBBBBB") {
+                    Ok(serde_json::json!({ "player_id": 1 }))
+                } else {
+                    Ok(serde_json::json!({
+                        "error": { "code": 16, "error": "Access level of this key is not high enough" }
+                    }))
+                }
+            }
+        }
+
+        let (storage, key) = setup(pool).await;
+        storage
+            .set_key_access_level(key.id, torn_api::key::access_level::PUBLIC)
+            .await
+            .unwrap();
+        let full = storage
+            .store_key(2, "BBBBBBBBBBBBBBBB".to_owned(), vec![Domain::All])
+            .await
+            .unwrap();
+        storage
+            .set_key_access_level(full.id, torn_api::key::access_level::FULL)
+            .await
+            .unwrap();
+        // make sure that the public key is tried first
+        for _ in 0..5 {
+            storage.acquire_key(KeySelector::Id(full.id)).await.unwrap();
+        }
+
+        let pool = PoolBuilder::new(AccessClient, storage).build();
+        let selector = KeySelector::OneOf(vec![Domain::All, Domain::User { id: 1 }]);
+
+        let response = pool.torn_api(selector.clone()).user(|b| b).await;
+        assert!(response.is_ok());
+
+        let response = pool
+            .execute_many_with_keys(
+                selector.clone(),
+                [1],
+                |b: ApiRequestBuilder<torn_api::user::Selection>| b,
+            )
+            .await;
+        assert!(matches!(response[&1], (Some(id), Ok(_)) if id == full.id));
+
+        // without a key of higher access level the error is returned as before
+        pool.storage
+            .remove_key(KeySelector::Id(full.id))
+            .await
+            .unwrap();
+        let response = pool.torn_api(selector).user(|b| b).await;
+        assert!(matches!(
+            response,
+            Err(KeyPoolError::Response(ResponseError::Api { code: 16, .. }))
+        ));
+    }
+
+    #[sqlx::test]
+    async fn warm(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+        let pool = PoolBuilder::new(StaticClient, storage).build();
+        let uses = || async {
+            pool.storage
+                .read_keys(Domain::All)
+                .await
+                .unwrap()
+                .iter()
+                .map(|k| k.uses)
+                .sum::<i16>()
+        };
+
+        let provider = pool.warm(Domain::All, 3).await.unwrap();
+        assert_eq!(uses().await, 3);
+
+        for _ in 0..3 {
+            provider.user(|b| b).await.unwrap();
+        }
+        assert_eq!(uses().await, 3);
+
+        provider.user(|b| b).await.unwrap();
+        assert_eq!(uses().await, 4);
+    }
+
+    #[sqlx::test]
+    async fn bulk_concurrency(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+        for i in 0..4 {
+            storage
+                .store_key(i, format!("{i:0>16}"), vec![Domain::All])
+                .await
+                .unwrap();
+        }
+        let pool = PoolBuilder::new(CountingClient::default(), storage)
+            .bulk_concurrency(25)
+            .build();
+
+        let response = pool
+            .execute_many_with_keys(
+                Domain::All,
+                0..1000,
+                |b: ApiRequestBuilder<torn_api::user::Selection>| b,
+            )
+            .await;
+
+        assert_eq!(response.len(), 1000);
+        assert!(response.values().all(|(_, res)| res.is_ok()));
+        assert!(
+            pool.client
+                .max_in_flight
+                .load(std::sync::atomic::Ordering::SeqCst)
+                <= 25
+        );
+
+        let uses: i16 = pool
+            .storage
+            .read_keys(Domain::All)
+            .await
+            .unwrap()
+            .iter()
+            .map(|k| k.uses)
+            .sum();
+        assert_eq!(uses, 1000);
+    }
+
+    #[sqlx::test]
+    async fn bulk_backlog(pool: PgPool) {
+        let (storage, _) = setup(pool).await;
+        let backlog = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let pool = PoolBuilder::new(CountingClient::default(), storage)
+            .bulk_concurrency(10)
+            .on_bulk_backlog({
+                let backlog = backlog.clone();
+                move |b| backlog.lock().unwrap().push(b)
+            })
+            .build();
+
+        let response = pool
+            .execute_many_with_keys(
+                Domain::All,
+                0..50,
+                |b: ApiRequestBuilder<torn_api::user::Selection>| b,
+            )
+            .await;
+        assert_eq!(response.len(), 50);
+
+        let backlog = backlog.lock().unwrap();
+        assert_eq!(
+            backlog.first(),
+            Some(&crate::BulkBacklog {
+                in_flight: 10,
+                waiting_for_key: 0,
+                queued: 40
+            })
+        );
+        assert!(backlog.iter().all(|b| b.in_flight <= 10));
+        assert_eq!(backlog.last().map(|b| b.queued), Some(0));
+    }
+
+    #[sqlx::test]
+    async fn default_query_items(pool: PgPool) {
+        let (storage, key) = setup(pool).await;
+        let pool = PoolBuilder::new(reqwest::Client::default(), storage)
+            .query_item("striptags", "false")
+            .query_item("limit", 10)
+            .build();
+
+        let (_, url) = pool
+            .dry_run(
+                Domain::All,
+                |b: ApiRequestBuilder<torn_api::user::Selection>| b.query_item("limit", 20),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            url,
+            format!(
+                "https://api.torn.com/user/?selections=&key={}&limit=20&striptags=false",
+                key.key
+            )
+        );
+    }
+
+    #[sqlx::test]
+    async fn dry_run(pool: PgPool) {
+        let (storage, key) = setup(pool).await;
+        let pool = PoolBuilder::new(reqwest::Client::default(), storage)
+            .comment("api.rs")
+            .build();
+
+        let (key_id, url) = pool
+            .dry_run(
+                Domain::All,
+                |b: ApiRequestBuilder<torn_api::user::Selection>| {
+                    b.id(1).selections([torn_api::user::Selection::Basic])
+                },
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(key_id, key.id);
+        assert_eq!(
+            url,
+            format!(
+                "https://api.torn.com/user/1?selections=basic&key={}&comment=api.rs",
+                key.key
+            )
+        );
+
+        let key = pool
+            .storage
+            .read_key(KeySelector::Id(key.id))
+            .await
+            .unwrap();
+        assert_eq!(key.unwrap().uses, 0);
+    }
+
     #[sqlx::test]
     async fn before_hook(pool: PgPool) {
         let (storage, _) = setup(pool).await;